@@ -18,9 +18,11 @@ mod database;
 mod error;
 mod logger;
 mod misc;
+mod queue;
 mod routes;
 mod session;
 mod state;
+mod watch;
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     trace::set_log_formatter(AfireLogger);
@@ -41,6 +43,8 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     routes::attach(&mut server);
 
     let app = server.app();
+    queue::start(app.clone());
+    watch::start(app.clone());
     let threads = app.config.server.threads;
     ctrlc::set_handler(move || {
         info!("Exiting");
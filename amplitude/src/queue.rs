@@ -0,0 +1,305 @@
+//! Test submissions are put in a queue and run by a pool of worker threads,
+//! separate from the ones handling requests, so running code never blocks the
+//! rest of the server. Users take turns, so one user submitting a lot of code
+//! doesn't hold everyone else up.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    panic::{self, AssertUnwindSafe},
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use amplitude_common::config::QueueConfig;
+use amplitude_markdown::items::ItemType;
+use amplitude_runner::{
+    exercise::{total_score, TestResults},
+    lang::Language,
+};
+use anyhow::Context;
+use parking_lot::{Condvar, Mutex};
+use serde::Serialize;
+use tracing::{error, trace};
+
+use crate::{misc::rand_str, state::State};
+
+pub struct Queue {
+    cfg: QueueConfig,
+    inner: Mutex<Inner>,
+    /// Notified when a job is queued
+    queued: Condvar,
+}
+
+#[derive(Default)]
+struct Inner {
+    /// Users with queued jobs, in the order they get to run their next one
+    users: VecDeque<String>,
+    /// Ids of the queued jobs of each user, oldest first
+    queued: HashMap<String, VecDeque<String>>,
+    jobs: HashMap<String, Job>,
+}
+
+struct Job {
+    user: String,
+    /// Taken by the worker that runs it
+    submission: Option<Submission>,
+    status: JobStatus,
+    /// When the job finished, so its results can be removed later
+    finished: Option<Instant>,
+}
+
+pub struct Submission {
+    /// The exercise's item id
+    pub exercise: String,
+    pub lang: Language,
+    pub code: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum JobStatus {
+    /// `position` is how many jobs will be run before this one
+    Queued {
+        position: usize,
+    },
+    Running,
+    Done {
+        results: HashMap<String, TestResults>,
+        /// The weighted score of the whole exercise, from 0 to 1
+        score: f64,
+    },
+    Failed {
+        error: String,
+    },
+}
+
+impl Queue {
+    pub fn new(cfg: QueueConfig) -> Self {
+        Self {
+            cfg,
+            inner: Mutex::new(Inner::default()),
+            queued: Condvar::new(),
+        }
+    }
+
+    /// Queue a submission and get its job id. Fails if the queue is full, or if
+    /// the user already has too many submissions waiting.
+    pub fn submit(&self, user: &str, submission: Submission) -> anyhow::Result<String> {
+        let mut inner = self.inner.lock();
+        inner.remove_expired(Duration::from_secs(self.cfg.keep_results));
+
+        let queued = inner.queued.values().map(|x| x.len()).sum::<usize>();
+        anyhow::ensure!(queued < self.cfg.capacity, "The queue is full");
+        let user_queued = inner.queued.get(user).map_or(0, |x| x.len());
+        anyhow::ensure!(
+            user_queued < self.cfg.per_user,
+            "You already have {user_queued} submissions waiting"
+        );
+
+        let id = rand_str(16);
+        inner.jobs.insert(
+            id.clone(),
+            Job {
+                user: user.to_string(),
+                submission: Some(submission),
+                status: JobStatus::Queued { position: 0 },
+                finished: None,
+            },
+        );
+        if user_queued == 0 {
+            inner.users.push_back(user.to_string());
+        }
+        inner
+            .queued
+            .entry(user.to_string())
+            .or_default()
+            .push_back(id.clone());
+
+        self.queued.notify_one();
+        Ok(id)
+    }
+
+    /// The status of a job, if it exists and was submitted by `user`
+    pub fn status(&self, id: &str, user: &str) -> Option<JobStatus> {
+        let inner = self.inner.lock();
+        let job = inner.jobs.get(id).filter(|job| job.user == user)?;
+        Some(match job.status {
+            JobStatus::Queued { .. } => JobStatus::Queued {
+                position: inner.position(user, id),
+            },
+            ref status => status.clone(),
+        })
+    }
+
+    /// Wait for a job to run, taking turns between users
+    fn next(&self) -> (String, Submission) {
+        let mut inner = self.inner.lock();
+        loop {
+            if let Some(user) = inner.users.pop_front() {
+                let queued = inner.queued.get_mut(&user).unwrap();
+                let id = queued.pop_front().unwrap();
+                match queued.is_empty() {
+                    true => {
+                        inner.queued.remove(&user);
+                    }
+                    false => inner.users.push_back(user),
+                }
+
+                let job = inner.jobs.get_mut(&id).unwrap();
+                job.status = JobStatus::Running;
+                return (id, job.submission.take().unwrap());
+            }
+            self.queued.wait(&mut inner);
+        }
+    }
+
+    fn finish(&self, id: &str, status: JobStatus) {
+        if let Some(job) = self.inner.lock().jobs.get_mut(id) {
+            job.status = status;
+            job.finished = Some(Instant::now());
+        }
+    }
+}
+
+impl Inner {
+    /// How many jobs will be run before the queued job `id` of `user`
+    fn position(&self, user: &str, id: &str) -> usize {
+        let index = self.queued[user].iter().position(|x| x == id).unwrap();
+        let turn = self.users.iter().position(|x| x == user).unwrap();
+        self.users
+            .iter()
+            .enumerate()
+            .filter(|(_, x)| *x != user)
+            .map(|(i, x)| {
+                // users before us in this round get one more turn
+                let turns = index + usize::from(i < turn);
+                self.queued[x].len().min(turns)
+            })
+            .sum::<usize>()
+            + index
+    }
+
+    fn remove_expired(&mut self, keep: Duration) {
+        self.jobs
+            .retain(|_, job| job.finished.is_none_or(|x| x.elapsed() < keep));
+    }
+}
+
+/// Start the worker threads
+pub fn start(app: Arc<State>) {
+    for i in 0..app.config.queue.workers {
+        let app = app.clone();
+        thread::Builder::new()
+            .name(format!("test-worker-{i}"))
+            .spawn(move || loop {
+                let (id, submission) = app.queue.next();
+                trace!("Running job `{id}` for `{}`", submission.exercise);
+                // a panic would kill the worker and leave the job running
+                // forever, so it fails the job instead
+                let result = panic::catch_unwind(AssertUnwindSafe(|| run(&app, &submission)));
+                let status = match result {
+                    Ok(Ok(results)) => JobStatus::Done {
+                        score: total_score(&results),
+                        results,
+                    },
+                    Ok(Err(e)) => {
+                        error!("Error running job `{id}`: {e:?}");
+                        JobStatus::Failed {
+                            error: format!("{e:?}"),
+                        }
+                    }
+                    Err(_) => {
+                        error!("Job `{id}` panicked");
+                        JobStatus::Failed {
+                            error: "Internal error while running the tests".to_string(),
+                        }
+                    }
+                };
+                app.queue.finish(&id, status);
+            })
+            .expect("Failed to start test worker");
+    }
+}
+
+fn run(app: &State, submission: &Submission) -> anyhow::Result<HashMap<String, TestResults>> {
+    // the item could have been removed since it was submitted, and it's cloned
+    // so the parse data isn't locked while the tests run
+    let item = app
+        .parse_data()
+        .items
+        .get(&submission.exercise)
+        .cloned()
+        .with_context(|| format!("Item `{}` not found", submission.exercise))?;
+    let ItemType::Exercise(e) = item else {
+        anyhow::bail!("Requested item is not an exercise");
+    };
+
+    let id = submission.exercise.rsplit_once('/').unwrap().1;
+    e.run_tests(&submission.lang, &submission.code, id, &app.config)
+        .context("Error running tests")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn queue(per_user: usize) -> Queue {
+        Queue::new(QueueConfig {
+            capacity: 4,
+            per_user,
+            ..Default::default()
+        })
+    }
+
+    fn submit(queue: &Queue, user: &str) -> anyhow::Result<String> {
+        queue.submit(
+            user,
+            Submission {
+                exercise: "course/exercise".to_string(),
+                lang: Language::Python,
+                code: String::new(),
+            },
+        )
+    }
+
+    #[test]
+    fn test_round_robin() {
+        let queue = queue(4);
+        let a1 = submit(&queue, "a").unwrap();
+        let a2 = submit(&queue, "a").unwrap();
+        let a3 = submit(&queue, "a").unwrap();
+        let b1 = submit(&queue, "b").unwrap();
+
+        assert!(matches!(
+            queue.status(&b1, "b"),
+            Some(JobStatus::Queued { position: 1 })
+        ));
+        assert!(matches!(
+            queue.status(&a3, "a"),
+            Some(JobStatus::Queued { position: 3 })
+        ));
+        // only the user that submitted a job can see it
+        assert!(queue.status(&b1, "a").is_none());
+
+        let order = (0..4).map(|_| queue.next().0).collect::<Vec<_>>();
+        assert_eq!(order, [a1, b1, a2, a3]);
+    }
+
+    #[test]
+    fn test_backpressure() {
+        let queue = queue(2);
+        submit(&queue, "a").unwrap();
+        submit(&queue, "a").unwrap();
+        assert!(submit(&queue, "a").is_err());
+
+        submit(&queue, "b").unwrap();
+        submit(&queue, "c").unwrap();
+        assert!(submit(&queue, "d").is_err());
+
+        // running a job makes room for another one
+        let (id, _) = queue.next();
+        assert!(matches!(queue.status(&id, "a"), Some(JobStatus::Running)));
+        submit(&queue, "d").unwrap();
+    }
+}
@@ -0,0 +1,128 @@
+//! Reloading the content from its git repository, either by an admin or by a
+//! push webhook from GitHub or Gitea. An admin's response has the commit
+//! that's checked out and the error if fetching or parsing it failed. Webhooks
+//! are answered right away and reload in the background, since GitHub gives
+//! up on them after 10 seconds.
+
+use amplitude_markdown::git::tracked_branch;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use super::*;
+
+use crate::{
+    error::StatusError,
+    session::get_session,
+    state::{Reload, State},
+};
+
+/// The parts of a push event's payload we need, which GitHub and Gitea send
+/// the same way
+#[derive(Deserialize, Debug)]
+struct PushEvent {
+    /// The ref that was pushed to, like `refs/heads/main`. Other events, like
+    /// GitHub's ping when a webhook is added, don't have one.
+    #[serde(rename = "ref")]
+    git_ref: Option<String>,
+}
+
+pub fn attach(server: &mut Server<State>) {
+    server.handled_stateful_route(Method::POST, "/api/admin/reload", |state, req| {
+        let session =
+            get_session(state.clone(), req).context(Status::Unauthorized, "Not logged in")?;
+        if !state.config.auth.admins.contains(&session.id) {
+            return error(Status::Forbidden, "Only admins can reload content");
+        }
+
+        reload_response(state.reload())
+    });
+
+    server.handled_stateful_route(Method::POST, "/api/webhook", |state, req| {
+        let secret = state
+            .config
+            .parse
+            .webhook_secret
+            .as_ref()
+            .context(Status::NotFound, "Webhook is not configured")?;
+        let signature = req
+            .headers
+            .get("X-Hub-Signature-256")
+            .and_then(|x| x.strip_prefix("sha256="))
+            .or_else(|| req.headers.get("X-Gitea-Signature"))
+            .context(Status::Unauthorized, "Missing signature")?;
+        if !verify_signature(secret.as_bytes(), &req.body, signature) {
+            return error(Status::Unauthorized, "Invalid signature");
+        }
+
+        let event: PushEvent = json(req).context(Status::BadRequest, "Invalid event")?;
+        let branch = tracked_branch(&state.config.parse).context(
+            Status::InternalServerError,
+            "While getting the branch of the repo",
+        )?;
+        match (event.git_ref, branch) {
+            (Some(git_ref), Some(branch)) if git_ref == format!("refs/heads/{branch}") => {
+                let text = match state.reload_in_background() {
+                    true => "Reloading",
+                    false => "Already reloading",
+                };
+                Ok(Response::new().status(Status::Accepted).text(text))
+            }
+            (Some(git_ref), _) => Ok(Response::new().text(format!("Ignored push to `{git_ref}`"))),
+            (None, _) => Ok(Response::new().text("Ignored event that isn't a push")),
+        }
+    });
+}
+
+fn reload_response(reload: Reload) -> Result<Response, StatusError> {
+    let status = match reload.error {
+        Some(_) => Status::InternalServerError,
+        None => Status::Ok,
+    };
+    Ok(Response::new()
+        .status(status)
+        .text(serde_json::to_string(&reload)?)
+        .content(Content::JSON))
+}
+
+/// Whether the hex encoded `signature` is the HMAC-SHA256 of `body` with
+/// `secret`, compared in constant time so it can't be guessed byte by byte
+fn verify_signature(secret: &[u8], body: &[u8], signature: &str) -> bool {
+    let Some(signature) = decode_hex(signature) else {
+        return false;
+    };
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC takes keys of any size");
+    mac.update(body);
+    mac.verify_slice(&signature).is_ok()
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.is_ascii() || !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_verify_signature() {
+        // from RFC 4231
+        let signature = "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843";
+        let body = b"what do ya want for nothing?";
+        assert!(verify_signature(b"Jefe", body, signature));
+        assert!(verify_signature(b"Jefe", body, &signature.to_uppercase()));
+        assert!(!verify_signature(b"Jeff", body, signature));
+        assert!(!verify_signature(b"Jefe", b"what do ya want?", signature));
+        assert!(!verify_signature(b"Jefe", body, &signature[1..]));
+        assert!(!verify_signature(b"Jefe", body, "é"));
+
+        let signature = "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54";
+        let body = b"Test Using Larger Than Block-Size Key - Hash Key First";
+        assert!(verify_signature(&[0xaa; 131], body, signature));
+    }
+}
@@ -7,6 +7,7 @@ use serde::de::DeserializeOwned;
 use serde::Deserialize;
 use tracing::trace;
 
+mod admin;
 mod auth;
 mod item;
 mod list;
@@ -19,6 +20,7 @@ pub fn attach(server: &mut Server<State>) {
             .text("Route not found")
     });
 
+    admin::attach(server);
     auth::attach(server);
     test::attach(server);
     list::attach(server);
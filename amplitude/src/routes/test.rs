@@ -1,9 +1,12 @@
+use std::sync::Arc;
+
 use amplitude_markdown::items::ItemType;
 use amplitude_runner::lang::Language;
+use serde_json::json;
 
 use super::*;
 
-use crate::{error::HandledRoute, state::State};
+use crate::{error::HandledRoute, queue::Submission, session::get_session, state::State};
 
 #[derive(Debug, Deserialize)]
 struct EvaluateReq {
@@ -20,16 +23,52 @@ pub fn attach(server: &mut Server<State>) {
         let item = parse_data
             .items
             .get(&body.id)
-            .with_context(Status::NotFound, || {
-                format!("Item `{}` not found", body.id)
-            })?;
-        let ItemType::Exercise(e) = item else { return error(Status::ExpectationFailed, "Requested item is not an exercise") };
+            .with_context(Status::NotFound, || format!("Item `{}` not found", body.id))?;
+        let ItemType::Exercise(_) = item else {
+            return error(
+                Status::ExpectationFailed,
+                "Requested item is not an exercise",
+            );
+        };
+        drop(parse_data);
+
+        let submission = Submission {
+            exercise: body.id,
+            lang: body.lang,
+            code: body.code,
+        };
+        let id = state
+            .queue
+            .submit(&user(state.clone(), req), submission)
+            .context(
+                Status::TooManyRequests,
+                "Too many submissions, try again later",
+            )?;
+
+        Ok(Response::new()
+            .status(Status::Accepted)
+            .text(json!({ "id": id }))
+            .content(Content::JSON))
+    });
 
-        let id = body.id.split_once('/').unwrap().1;
-        let results = e.run_tests(&body.lang, &body.code, id, &state.config).context(Status::InternalServerError, "Error running tests")?;
+    server.handled_stateful_route(Method::GET, "/api/test/{id}", |state, req| {
+        let id = req.param("id").unwrap();
+        let status = state
+            .queue
+            .status(&id, &user(state.clone(), req))
+            .with_context(Status::NotFound, || format!("Job `{id}` not found"))?;
 
         Ok(Response::new()
-            .text(serde_json::to_string(&results)?)
+            .text(serde_json::to_string(&status)?)
             .content(Content::JSON))
     });
 }
+
+/// Who submitted a request, for taking turns in the queue. Users that aren't
+/// logged in go by their ip address.
+fn user(state: Arc<State>, req: &Request) -> String {
+    match get_session(state, req) {
+        Ok(session) => session.id,
+        Err(_) => req.address.ip().to_string(),
+    }
+}
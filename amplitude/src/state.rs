@@ -1,18 +1,35 @@
-use std::{fs, path::PathBuf};
+use std::{fs, path::PathBuf, sync::Arc, thread};
 
 use amplitude_common::config::{Args, Config};
 use parking_lot::{Mutex, MutexGuard, RwLock, RwLockReadGuard};
 use rusqlite::Connection;
 
-use crate::database::Database;
+use crate::{database::Database, queue::Queue};
 
 use amplitude_common::path;
-use amplitude_markdown::parse::{parse, ParseData};
+use amplitude_markdown::{
+    git::{head_commit, restore_commit, sync_repo},
+    parse::{parse, parse_courses, ParseData},
+};
+use serde::Serialize;
+use tracing::{error, info};
 
 pub struct State {
     db: Mutex<Connection>,
     pub parse_data: RwLock<ParseData>,
+    pub queue: Queue,
     pub config: Config,
+    /// Held while reloading, so only one reload runs at a time
+    reloading: Mutex<()>,
+}
+
+/// What happened when reloading the content, see [`State::reload`]
+#[derive(Serialize, Debug)]
+pub struct Reload {
+    /// The commit that's checked out afterwards, if fetching worked
+    pub commit: Option<String>,
+    /// Why fetching or parsing failed, in which case the old content is kept
+    pub error: Option<String>,
 }
 
 impl State {
@@ -38,10 +55,101 @@ impl State {
         Ok(Self {
             db: Mutex::new(db),
             parse_data: RwLock::new(parse_data),
+            queue: Queue::new(config.queue.clone()),
             config,
+            reloading: Mutex::new(()),
         })
     }
 
+    /// Fetch new commits into the clone and parse everything again, only
+    /// replacing the parse data if that works. Otherwise the commit that was
+    /// checked out before is checked out again, so the clone matches what's
+    /// served. With `--local`, nothing is fetched so local changes aren't
+    /// lost.
+    pub fn reload(&self) -> Reload {
+        let _reloading = self.reloading.lock();
+        self.reload_locked()
+    }
+
+    /// Start a [`State::reload`] on another thread, unless one is already
+    /// running. Returns whether it was started.
+    pub fn reload_in_background(self: &Arc<Self>) -> bool {
+        if self.reloading.is_locked() {
+            return false;
+        }
+
+        let app = self.clone();
+        thread::Builder::new()
+            .name("reload".to_string())
+            .spawn(move || {
+                // errors are logged by `reload_locked`
+                match app.reloading.try_lock() {
+                    Some(_reloading) => {
+                        app.reload_locked();
+                    }
+                    None => info!("Skipping reload, another one just started"),
+                }
+            })
+            .expect("Failed to start reload");
+        true
+    }
+
+    fn reload_locked(&self) -> Reload {
+        let live = head_commit(&self.config.parse);
+        let commit = if self.config.args.local {
+            head_commit(&self.config.parse)
+        } else {
+            sync_repo(&self.config.parse)
+        };
+        let commit = match commit {
+            Ok(commit) => commit,
+            Err(e) => {
+                error!("Error fetching new commits: {e:?}");
+                return Reload {
+                    commit: None,
+                    error: Some(format!("{e:?}")),
+                };
+            }
+        };
+
+        match parse_courses(&self.config) {
+            Ok(parse_data) => {
+                info!("Reloaded content at commit `{commit}`");
+                *self.parse_data.write() = parse_data;
+                Reload {
+                    commit: Some(commit),
+                    error: None,
+                }
+            }
+            Err(e) => {
+                error!("Error parsing commit `{commit}`, keeping the old content: {e:?}");
+                let live = live.and_then(|live| match live == commit {
+                    true => Ok(live),
+                    false => restore_commit(&self.config.parse, &live).map(|_| live),
+                });
+                let (live, error) = match live {
+                    Ok(live) => {
+                        let error =
+                            format!("Commit `{commit}` doesn't parse, `{live}` is still live");
+                        (live, error)
+                    }
+                    Err(restore) => {
+                        error!("Error checking out the old commit again: {restore:?}");
+                        let error = format!(
+                            "Commit `{commit}` doesn't parse, and the old commit couldn't be \
+                             checked out again, so the clone doesn't match what's live"
+                        );
+                        (commit, error)
+                    }
+                };
+                Reload {
+                    commit: Some(live),
+                    error: Some(format!("{error}: {e:?}")),
+                }
+            }
+        }
+    }
+
     pub fn db(&self) -> MutexGuard<Connection> {
         self.db.lock()
     }
@@ -0,0 +1,232 @@
+//! Reparses content when it changes, if `[parse.watch]` is set in the config.
+//! `clone_path` is polled for files that were added, removed or modified, and
+//! only the items or courses they're part of are reparsed. If that fails, the
+//! error is logged and the old version is kept.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+    thread,
+    time::{Duration, SystemTime},
+};
+
+use amplitude_markdown::parse::{course::item_id, parse_one_course, parse_one_item};
+use amplitude_runner::lock::LOCKFILE;
+use tracing::{error, info};
+
+use crate::state::State;
+
+/// When every file was last modified
+type Snapshot = BTreeMap<PathBuf, SystemTime>;
+
+/// What has to be reparsed because of a change
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Change {
+    /// The course with this id
+    Course(String),
+    /// The item at `path`, which is in the track at `track`
+    Item {
+        course: String,
+        track: PathBuf,
+        path: PathBuf,
+    },
+}
+
+pub fn start(app: Arc<State>) {
+    let Some(watch) = app.config.parse.watch.clone() else {
+        return;
+    };
+    let root = PathBuf::from(&app.config.parse.clone_path);
+    info!("Watching `{}` for changes", root.display());
+
+    thread::Builder::new()
+        .name("watcher".to_string())
+        .spawn(move || {
+            let mut old = snapshot(&root);
+            loop {
+                thread::sleep(Duration::from_millis(watch.interval));
+                let new = snapshot(&root);
+                for change in changes(&root, &old, &new) {
+                    reparse(&app, &root, change);
+                }
+                old = new;
+            }
+        })
+        .expect("Failed to start watcher");
+}
+
+fn snapshot(root: &Path) -> Snapshot {
+    fn walk(path: &Path, out: &mut Snapshot) {
+        let Ok(entries) = fs::read_dir(path) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            // the parser skips hidden files, and lockfiles are written while
+            // parsing, which would reparse exercises twice
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            if name.starts_with('.') || name == LOCKFILE {
+                continue;
+            }
+
+            let Ok(meta) = entry.metadata() else {
+                continue;
+            };
+            if meta.is_dir() {
+                walk(&entry.path(), out);
+            } else if let Ok(modified) = meta.modified() {
+                out.insert(entry.path(), modified);
+            }
+        }
+    }
+
+    let mut out = Snapshot::new();
+    walk(root, &mut out);
+    out
+}
+
+/// An item is reparsed on its own when a file in it changed and it existed
+/// before and after, anything else reparses the whole course, since the
+/// order of its tracks and items could have changed.
+fn changes(root: &Path, old: &Snapshot, new: &Snapshot) -> BTreeSet<Change> {
+    let changed = old
+        .iter()
+        .filter(|(path, time)| new.get(*path) != Some(time))
+        .map(|(path, _)| path)
+        .chain(new.keys().filter(|path| !old.contains_key(*path)));
+
+    let mut out = BTreeSet::new();
+    for path in changed {
+        let Ok(parts) = path.strip_prefix(root) else {
+            continue;
+        };
+        let parts = parts.iter().collect::<Vec<_>>();
+        // files directly in `clone_path` aren't part of a course
+        if parts.len() < 2 {
+            continue;
+        }
+
+        let course = parts[0].to_string_lossy().to_string();
+        if parts.len() > 2 && parts[2] != "track.toml" {
+            let track = root.join(parts[0]).join(parts[1]);
+            let item = track.join(parts[2]);
+            let exists = |snapshot: &Snapshot| snapshot.keys().any(|x| x.starts_with(&item));
+            if track.join("track.toml").exists() && exists(old) && exists(new) {
+                out.insert(Change::Item {
+                    course,
+                    track,
+                    path: item,
+                });
+                continue;
+            }
+        }
+        out.insert(Change::Course(course));
+    }
+
+    // items are reparsed with their course anyway
+    let courses = out
+        .iter()
+        .filter_map(|change| match change {
+            Change::Course(id) => Some(id.clone()),
+            Change::Item { .. } => None,
+        })
+        .collect::<BTreeSet<_>>();
+    out.retain(|change| match change {
+        Change::Course(_) => true,
+        Change::Item { course, .. } => !courses.contains(course),
+    });
+    out
+}
+
+fn reparse(app: &State, root: &Path, change: Change) {
+    match change {
+        Change::Course(id) => {
+            let path = root.join(&id);
+            if !path.exists() {
+                info!("Removing course `{id}`");
+                app.parse_data.write().replace_course(&id, None);
+                return;
+            }
+
+            info!("Reparsing course `{id}`");
+            match parse_one_course(&app.config, &path) {
+                Ok(data) => app.parse_data.write().replace_course(&id, Some(data)),
+                Err(e) => error!("Error reparsing course `{id}`: {e:?}"),
+            }
+        }
+        Change::Item {
+            course,
+            track,
+            path,
+        } => {
+            let id = format!("{course}/{}", item_id(&path));
+            info!("Reparsing item `{id}`");
+            match parse_one_item(&app.config, &root.join(&course), &track, &path) {
+                Ok(data) => app.parse_data.write().replace_item(&id, data),
+                Err(e) => error!("Error reparsing item `{id}`: {e:?}"),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        let track = root.join("course/01_basics");
+        fs::create_dir_all(track.join("02_exercise")).unwrap();
+        fs::create_dir_all(root.join("other")).unwrap();
+        for file in [
+            "course/course.toml",
+            "course/01_basics/track.toml",
+            "course/01_basics/01_intro.md",
+            "course/01_basics/02_exercise/config.toml",
+            "other/course.toml",
+        ] {
+            fs::write(root.join(file), "").unwrap();
+        }
+
+        let old = snapshot(root);
+        let mut new = old.clone();
+        let later = SystemTime::now() + Duration::from_secs(1);
+        let item = |path: &str| Change::Item {
+            course: "course".to_string(),
+            track: track.clone(),
+            path: track.join(path),
+        };
+
+        // a file in an exercise that was there before
+        new.insert(track.join("02_exercise/fixtures/a.txt"), later);
+        assert_eq!(
+            changes(root, &old, &new),
+            BTreeSet::from([item("02_exercise")])
+        );
+
+        new.insert(track.join("01_intro.md"), later);
+        new.insert(root.join("other/course.toml"), later);
+        assert_eq!(
+            changes(root, &old, &new),
+            BTreeSet::from([
+                item("01_intro.md"),
+                item("02_exercise"),
+                Change::Course("other".to_string()),
+            ])
+        );
+
+        // a new item changes the order of the track
+        new.insert(track.join("03_outro.md"), later);
+        assert_eq!(
+            changes(root, &old, &new),
+            BTreeSet::from([
+                Change::Course("course".to_string()),
+                Change::Course("other".to_string()),
+            ])
+        );
+    }
+}
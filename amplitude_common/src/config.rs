@@ -1,7 +1,7 @@
 use std::{collections::HashMap, fs};
 
 use clap::Parser;
-use serde::Deserialize;
+use serde::{Deserialize, Deserializer};
 
 use crate::path;
 
@@ -13,6 +13,10 @@ pub struct Args {
     /// The path of the config file
     #[arg(long, default_value_t = {"config.toml".to_string()})]
     pub config: String,
+    /// Regenerate the test cases of every exercise, and fail if they differ
+    /// from its `tests.lock.json` instead of using or updating it
+    #[arg(long, default_value_t = false)]
+    pub check_locks: bool,
 }
 
 impl Args {
@@ -25,8 +29,37 @@ impl Args {
 #[derive(Deserialize, Debug)]
 #[serde(deny_unknown_fields)]
 pub struct ParseConfig {
+    /// The content repo, which can be a `file://` url to a local repo
     pub git_url: String,
     pub clone_path: String,
+    /// The branch to check out. Without a `branch`, `tag` or `commit`, it's
+    /// the remote's default branch.
+    pub branch: Option<String>,
+    /// A tag to pin the content to
+    pub tag: Option<String>,
+    /// The full id of a commit to pin the content to
+    pub commit: Option<String>,
+    /// Only fetch this many commits, for a shallow clone
+    pub depth: Option<u32>,
+    /// Reparse content when it changes, see [`WatchConfig`]
+    pub watch: Option<WatchConfig>,
+    /// The secret push webhooks to `/api/webhook` are signed with. The
+    /// webhook is disabled without one.
+    pub webhook_secret: Option<String>,
+}
+
+/// Watches `clone_path` for changes while the server is running, and reparses
+/// the course or item that changed
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct WatchConfig {
+    /// How often to check for changes, in milliseconds
+    #[serde(default = "watch_interval_default")]
+    pub interval: u64,
+}
+
+const fn watch_interval_default() -> u64 {
+    1000
 }
 
 #[derive(Deserialize, Debug)]
@@ -38,15 +71,39 @@ pub struct ServerConfig {
     pub req_duration: u64,
 }
 
+/// The queue test submissions wait in before they are run
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct QueueConfig {
+    /// Threads running tests, separate from the ones handling requests
+    pub workers: usize,
+    /// How many submissions can wait to be run before new ones are rejected
+    pub capacity: usize,
+    /// How many submissions a single user can have waiting
+    pub per_user: usize,
+    /// How long the results of a submission are kept, in seconds
+    pub keep_results: u64,
+}
+
+impl Default for QueueConfig {
+    fn default() -> Self {
+        Self {
+            workers: 4,
+            capacity: 64,
+            per_user: 2,
+            keep_results: 5 * 60,
+        }
+    }
+}
+
 #[derive(Deserialize, Default, Debug)]
 #[serde(deny_unknown_fields)]
 pub struct AuthConfig {
     pub google_oauth: Option<GoogleOauth>,
     pub github_oauth: Option<GithubOauth>,
-}
-
-fn args() -> Args {
-    Args::parse()
+    /// Ids of the users that can use admin routes, like reloading content
+    #[serde(default)]
+    pub admins: Vec<String>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -57,29 +114,127 @@ pub struct Config {
     #[serde(default)]
     pub auth: AuthConfig,
     pub parse: ParseConfig,
-    #[serde(default = "args")]
+    #[serde(default)]
+    pub queue: QueueConfig,
+    /// Set from the command line after the config is loaded
+    #[serde(skip)]
     pub args: Args,
 }
 
 fn language_config() -> HashMap<String, LanguageConfig> {
-    toml::from_str(
-        fs::read_to_string(path::LANGUAGES.join("languages.toml"))
-            .unwrap()
-            .as_str(),
+    with_names(
+        toml::from_str(
+            fs::read_to_string(path::LANGUAGES.join("languages.toml"))
+                .unwrap()
+                .as_str(),
+        )
+        .unwrap(),
     )
-    .unwrap()
+}
+
+fn deserialize_language_config<'de, D>(
+    deserializer: D,
+) -> Result<HashMap<String, LanguageConfig>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    HashMap::deserialize(deserializer).map(with_names)
+}
+
+fn with_names(mut langs: HashMap<String, LanguageConfig>) -> HashMap<String, LanguageConfig> {
+    for (name, lang) in langs.iter_mut() {
+        lang.name = name.clone();
+    }
+    langs
 }
 
 #[derive(Deserialize, Debug)]
 #[serde(deny_unknown_fields)]
 pub struct DockerConfig {
     pub tmp_folder: String,
-    pub command: String,
+    #[serde(default)]
+    pub backend: Backend,
+    /// The command for container backends, defaults to the name of the backend
+    pub command: Option<String>,
     pub timeout: u64,
-    #[serde(default = "language_config")]
+    /// How many test cases of a stdin / stdout exercise run at once, each in
+    /// its own container
+    #[serde(default = "io_runs_default")]
+    pub io_runs: usize,
+    /// Keep containers running in the background, see [`PoolConfig`]
+    pub pool: Option<PoolConfig>,
+    /// How much output is kept, see [`OutputConfig`]
+    #[serde(default)]
+    pub output: OutputConfig,
+    #[serde(
+        default = "language_config",
+        deserialize_with = "deserialize_language_config"
+    )]
     pub language_config: HashMap<String, LanguageConfig>,
 }
 
+const fn io_runs_default() -> usize {
+    2
+}
+
+/// Containers kept running in the background, so jobs don't have to wait for
+/// one to start. Only used by the container backends.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct PoolConfig {
+    /// How many idle containers to keep for each language
+    #[serde(default = "pool_size_default")]
+    pub size: usize,
+    /// How many jobs a container runs before it is replaced. With `1` every
+    /// job still gets a fresh container, it just doesn't have to wait for it.
+    #[serde(default = "pool_max_uses_default")]
+    pub max_uses: u32,
+    /// The languages to keep containers for, all of them if empty
+    #[serde(default)]
+    pub languages: Vec<String>,
+}
+
+const fn pool_size_default() -> usize {
+    2
+}
+
+const fn pool_max_uses_default() -> u32 {
+    1
+}
+
+/// Limits on how much output is kept from running code, in bytes
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct OutputConfig {
+    /// Of stdout and of stderr of a whole run. Runs that print more fail with
+    /// an output limit verdict.
+    pub run: usize,
+    /// Of the output of a single test case shown to students, anything after
+    /// it is cut off
+    pub test: usize,
+}
+
+impl Default for OutputConfig {
+    fn default() -> Self {
+        Self {
+            run: 4 << 20,
+            test: 16 << 10,
+        }
+    }
+}
+
+/// Where code gets run
+#[derive(Deserialize, Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Backend {
+    #[default]
+    Docker,
+    Podman,
+    /// A plain process with some resource limits, without any isolation.
+    /// Only meant for development.
+    Local,
+}
+
 #[derive(Deserialize, Debug)]
 #[serde(deny_unknown_fields)]
 pub struct GoogleOauth {
@@ -98,6 +253,9 @@ pub struct GithubOauth {
 #[derive(Deserialize, Debug)]
 #[serde(deny_unknown_fields)]
 pub struct LanguageConfig {
+    /// The language's key in `languages.toml`, which is also its directory
+    #[serde(skip)]
+    pub name: String,
     pub image_name: String,
     pub source_path: String,
     pub extension: String,
@@ -2,12 +2,24 @@
 
 use std::{env, fs, path::PathBuf};
 
+use anyhow::Context;
 use config::{Args, Config};
 
 pub mod config;
 pub mod path;
 
 pub fn config_and_set_path() -> anyhow::Result<config::Config> {
+    set_path()?;
+    let args = Args::parse();
+    let mut config = load_config(&args.config)?;
+    config.args = args;
+
+    Ok(config)
+}
+
+/// Move into the `amplitude` directory, so paths in the config and
+/// [`path`] work from anywhere inside it
+pub fn set_path() -> anyhow::Result<()> {
     let binding = env::current_dir()?;
     let dir = binding
         .components()
@@ -15,11 +27,11 @@ pub fn config_and_set_path() -> anyhow::Result<config::Config> {
         .collect::<PathBuf>()
         .join("amplitude");
     env::set_current_dir(dir)?;
+    Ok(())
+}
 
-    let args = Args::parse();
-    let mut config: Config = toml::from_str::<Config>(&fs::read_to_string(&args.config)?)
-        .expect("Not a valid config file");
-    config.args = args;
-
-    Ok(config)
+/// Load a config file, without looking at the command line
+pub fn load_config(path: &str) -> anyhow::Result<Config> {
+    let src = fs::read_to_string(path).with_context(|| format!("While reading `{path}`"))?;
+    toml::from_str(&src).with_context(|| format!("While parsing `{path}`"))
 }
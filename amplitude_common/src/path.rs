@@ -11,6 +11,7 @@ macro def_static($name:ident, $str:literal) {
 
 def_static!(DATABASE, "./data.db");
 def_static!(LANGUAGES, "./languages");
+def_static!(CACHE, "./cache");
 
 /// A struct that represents a path, which can be initialized statically.
 #[derive(Clone, Default)]
@@ -0,0 +1,283 @@
+//! Keeping the clone of the content repo at `clone_path` up to date. An
+//! existing clone of the same remote is fetched into instead of cloning again,
+//! and the branch, tag or commit in the config is checked out. Branches are
+//! only ever fast-forwarded, and changes to tracked files in the clone are
+//! overwritten.
+
+use std::{fs, path::Path};
+
+use amplitude_common::config::ParseConfig;
+use anyhow::Context;
+use git2::{build::CheckoutBuilder, BranchType, Commit, Direction, FetchOptions, Oid, Repository};
+use tracing::info;
+
+/// What to check out, from the config
+enum Rev<'a> {
+    Branch(String),
+    Tag(&'a str),
+    Commit(&'a str),
+}
+
+/// Fetch the branch, tag or commit from the config into the clone at
+/// `clone_path` and check it out, cloning the repo first if it isn't there.
+/// Returns the id of the commit that's checked out.
+pub fn sync_repo(config: &ParseConfig) -> anyhow::Result<String> {
+    let path = Path::new(&config.clone_path);
+    let repo = match open_clone(config) {
+        Some(repo) => repo,
+        None => {
+            info!("Cloning `{}` into `{}`", config.git_url, config.clone_path);
+            clear_dir(path).context("While deleting old clone")?;
+            let repo = Repository::init(path).context("While creating repo")?;
+            repo.remote("origin", &config.git_url)?;
+            repo
+        }
+    };
+    let mut remote = repo.find_remote("origin")?;
+
+    // the local transport can't do shallow fetches, and doesn't need them
+    let mut options = FetchOptions::new();
+    let local = config.git_url.starts_with("file://");
+    if let Some(depth) = config.depth.filter(|_| !local) {
+        options.depth(i32::try_from(depth).context("`depth` is too large")?);
+    }
+
+    let rev = match (&config.branch, &config.tag, &config.commit) {
+        (None, None, None) => {
+            remote.connect(Direction::Fetch)?;
+            let default = remote.default_branch()?;
+            let default = default.as_str().context("Default branch isn't UTF-8")?;
+            let branch = default.strip_prefix("refs/heads/").unwrap_or(default);
+            let branch = branch.to_string();
+            remote.disconnect()?;
+            Rev::Branch(branch)
+        }
+        (Some(branch), None, None) => Rev::Branch(branch.clone()),
+        (None, Some(tag), None) => Rev::Tag(tag),
+        (None, None, Some(commit)) => Rev::Commit(commit),
+        _ => anyhow::bail!("Only one of `branch`, `tag` and `commit` can be set"),
+    };
+
+    let commit = match &rev {
+        Rev::Branch(branch) => {
+            let refspec = format!("+refs/heads/{branch}:refs/remotes/origin/{branch}");
+            remote
+                .fetch(&[refspec.as_str()], Some(&mut options), None)
+                .with_context(|| format!("While fetching branch `{branch}`"))?;
+            let commit = repo
+                .find_reference(&format!("refs/remotes/origin/{branch}"))?
+                .peel_to_commit()?;
+            check_fast_forward(&repo, branch, &commit)?;
+            commit
+        }
+        Rev::Tag(tag) => {
+            let refspec = format!("+refs/tags/{tag}:refs/tags/{tag}");
+            remote
+                .fetch(&[refspec.as_str()], Some(&mut options), None)
+                .with_context(|| format!("While fetching tag `{tag}`"))?;
+            repo.find_reference(&format!("refs/tags/{tag}"))?
+                .peel_to_commit()?
+        }
+        Rev::Commit(commit) => {
+            anyhow::ensure!(
+                commit.len() == 40,
+                "`commit` has to be a full commit id, not `{commit}`"
+            );
+            let id = Oid::from_str(commit)?;
+            // pinned commits never change, so they're only fetched once
+            if repo.find_commit(id).is_err() {
+                remote
+                    .fetch(&[*commit], Some(&mut options), None)
+                    .with_context(|| format!("While fetching commit `{commit}`"))?;
+            }
+            repo.find_commit(id)?
+        }
+    };
+
+    let branch = match &rev {
+        Rev::Branch(branch) => Some(branch.as_str()),
+        Rev::Tag(_) | Rev::Commit(_) => None,
+    };
+    checkout(&repo, &commit, branch).context("While checking out")?;
+
+    Ok(commit.id().to_string())
+}
+
+/// Make sure the local `branch` can be fast-forwarded to `commit`, so history
+/// that was rewritten upstream never replaces what's checked out
+fn check_fast_forward(repo: &Repository, branch: &str, commit: &Commit) -> anyhow::Result<()> {
+    let Ok(local) = repo.find_branch(branch, BranchType::Local) else {
+        return Ok(());
+    };
+    let old = local.get().peel_to_commit()?.id();
+    anyhow::ensure!(
+        old == commit.id() || repo.graph_descendant_of(commit.id(), old)?,
+        "`{branch}` can't be fast-forwarded from {old} to {}, delete `clone_path` to clone it \
+         again",
+        commit.id()
+    );
+    Ok(())
+}
+
+/// The branch whose pushes update the content, which is `None` if it's pinned
+/// to a tag or a commit
+pub fn tracked_branch(config: &ParseConfig) -> anyhow::Result<Option<String>> {
+    if config.tag.is_some() || config.commit.is_some() {
+        return Ok(None);
+    }
+    if let Some(branch) = &config.branch {
+        return Ok(Some(branch.clone()));
+    }
+
+    let repo = Repository::open(&config.clone_path).context("While opening repo")?;
+    anyhow::ensure!(!repo.head_detached()?, "Repo is not on a branch");
+    let head = repo.head()?;
+    let branch = head.shorthand().context("Repo is not on a branch")?;
+    Ok(Some(branch.to_string()))
+}
+
+/// The id of the commit the clone at `clone_path` has checked out
+pub fn head_commit(config: &ParseConfig) -> anyhow::Result<String> {
+    let repo = Repository::open(&config.clone_path).context("While opening repo")?;
+    let commit = repo.head()?.peel_to_commit()?;
+    Ok(commit.id().to_string())
+}
+
+/// Check out `commit` in the clone at `clone_path` again, moving the branch
+/// that's checked out back to it
+pub fn restore_commit(config: &ParseConfig, commit: &str) -> anyhow::Result<()> {
+    let repo = Repository::open(&config.clone_path).context("While opening repo")?;
+    let commit = repo.find_commit(Oid::from_str(commit)?)?;
+    let branch = match repo.head_detached()? {
+        true => None,
+        false => repo.head()?.shorthand().map(str::to_string),
+    };
+    checkout(&repo, &commit, branch.as_deref()).context("While checking out")
+}
+
+/// The repo at `clone_path`, if it's a clone of `git_url`
+fn open_clone(config: &ParseConfig) -> Option<Repository> {
+    let repo = Repository::open(&config.clone_path).ok()?;
+    let url = repo.find_remote("origin").ok()?.url()?.to_string();
+    (url == config.git_url).then_some(repo)
+}
+
+/// Check out `commit`, moving `branch` to it if there is one. Files changed
+/// in the working tree are overwritten.
+fn checkout(repo: &Repository, commit: &Commit, branch: Option<&str>) -> anyhow::Result<()> {
+    // HEAD is detached first, since the branch that's checked out can't be
+    // moved
+    repo.set_head_detached(commit.id())?;
+    repo.checkout_head(Some(CheckoutBuilder::new().force()))?;
+
+    if let Some(branch) = branch {
+        repo.branch(branch, commit, true)?;
+        repo.set_head(&format!("refs/heads/{branch}"))?;
+    }
+    Ok(())
+}
+
+/// Delete everything in `path`, creating it if it doesn't exist
+fn clear_dir(path: &Path) -> anyhow::Result<()> {
+    fs::create_dir_all(path)?;
+    for item in fs::read_dir(path)? {
+        let item = item?;
+        let path = item.path();
+        if path.is_dir() {
+            fs::remove_dir_all(path)?;
+        } else {
+            fs::remove_file(path)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use git2::{RepositoryInitOptions, Signature};
+
+    use super::*;
+
+    /// Commit `content` to `file` on the branch that's checked out
+    fn commit(repo: &Repository, file: &str, content: &str) -> Oid {
+        let path = repo.workdir().unwrap().join(file);
+        fs::write(path, content).unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new(file)).unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        index.write().unwrap();
+
+        let sig = Signature::now("test", "test@example.com").unwrap();
+        let parent = repo.head().ok().map(|x| x.peel_to_commit().unwrap());
+        let parents = parent.iter().collect::<Vec<_>>();
+        repo.commit(Some("HEAD"), &sig, &sig, content, &tree, &parents)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_sync_repo() {
+        let dir = tempfile::tempdir().unwrap();
+        let origin = Repository::init_opts(
+            dir.path().join("origin"),
+            RepositoryInitOptions::new().initial_head("main"),
+        )
+        .unwrap();
+        let first = commit(&origin, "a.md", "1");
+        let first_commit = origin.find_object(first, None).unwrap();
+        origin.tag_lightweight("v1", &first_commit, false).unwrap();
+        let second = commit(&origin, "a.md", "2");
+
+        let clone = dir.path().join("clone");
+        let mut config = ParseConfig {
+            git_url: format!("file://{}", dir.path().join("origin").display()),
+            clone_path: clone.to_string_lossy().to_string(),
+            branch: None,
+            tag: None,
+            commit: None,
+            depth: None,
+            watch: None,
+            webhook_secret: None,
+        };
+        let file = || fs::read_to_string(clone.join("a.md")).unwrap();
+
+        // the default branch, then new commits on it
+        assert_eq!(sync_repo(&config).unwrap(), second.to_string());
+        assert_eq!(tracked_branch(&config).unwrap().as_deref(), Some("main"));
+        let third = commit(&origin, "a.md", "3");
+        assert_eq!(sync_repo(&config).unwrap(), third.to_string());
+        assert_eq!(file(), "3");
+
+        config.tag = Some("v1".to_string());
+        assert_eq!(sync_repo(&config).unwrap(), first.to_string());
+        assert_eq!(file(), "1");
+        assert_eq!(tracked_branch(&config).unwrap(), None);
+
+        config.tag = None;
+        config.commit = Some(second.to_string());
+        assert_eq!(sync_repo(&config).unwrap(), second.to_string());
+        assert_eq!(head_commit(&config).unwrap(), second.to_string());
+
+        config.commit = None;
+        config.branch = Some("main".to_string());
+        assert_eq!(sync_repo(&config).unwrap(), third.to_string());
+        assert_eq!(file(), "3");
+
+        // going back a commit keeps the branch, and it's fast-forwarded again
+        restore_commit(&config, &second.to_string()).unwrap();
+        assert_eq!(file(), "2");
+        assert_eq!(tracked_branch(&config).unwrap().as_deref(), Some("main"));
+        assert_eq!(sync_repo(&config).unwrap(), third.to_string());
+
+        // rewriting history upstream isn't a fast-forward
+        origin
+            .reset(&first_commit, git2::ResetType::Hard, None)
+            .unwrap();
+        commit(&origin, "a.md", "rewritten");
+        assert!(sync_repo(&config)
+            .unwrap_err()
+            .to_string()
+            .contains("can't be fast-forwarded"));
+        assert_eq!(head_commit(&config).unwrap(), third.to_string());
+        assert_eq!(file(), "3");
+    }
+}
@@ -3,14 +3,15 @@ use super::*;
 use crate::parse::parse_md;
 pub use amplitude_runner::exercise::Exercise;
 use amplitude_runner::{
-    exercise::{generate, runner_template, ExerciseConfig, LanguageInfo},
+    cache::{generate_cached, verify_cached},
+    constraints::generate_from_constraints,
+    exercise::{runner_template, Checker, ExerciseConfig, LanguageInfo},
     lang::Language,
+    lock::{locked_test_cases, LOCKFILE},
+    stub::{check_stub, generate_stub},
 };
-use std::{
-    collections::{hash_map::DefaultHasher, HashMap},
-    hash::{Hash, Hasher},
-    str::FromStr,
-};
+use enum_iterator::all;
+use std::{collections::HashMap, str::FromStr};
 
 impl FromDirectory for Exercise {
     fn from_directory(
@@ -24,63 +25,184 @@ impl FromDirectory for Exercise {
         let src = DirectoryContent::new(&src_path)?;
 
         let id = context.id().rsplit_once('/').unwrap().1.to_string();
-        let starting_code = src.query_files(&id, FileType::Code)?;
-        let generator = src
-            .query_files("generator", FileType::Code)?
-            .collect::<Vec<_>>();
+        let checker = src
+            .query_optional_file("checker", FileType::Code)?
+            .map(|item| -> anyhow::Result<_> {
+                Ok(Checker {
+                    lang: Language::from_str(&item.ext)?,
+                    code: item.read_to_string()?,
+                })
+            })
+            .transpose()
+            .context("While reading checker file")?;
 
+        let config_src = config.read_to_string()?;
         let mut config: ExerciseConfig =
-            toml::from_str(&config.read_to_string()?).context("While parsing `config.toml`")?;
-
-        let mut hasher = DefaultHasher::new();
-        id.hash(&mut hasher);
-        // set seed of function configs
-        for (func, cfg) in config.functions.iter_mut() {
-            let mut h = hasher.clone();
-            func.hash(&mut h);
-            cfg.seed = h.finish();
-        }
+            toml::from_str(&config_src).context("While parsing `config.toml`")?;
+        config.check().context("Invalid `config.toml`")?;
 
-        let lang = Language::from_str(&generator[0].ext)?;
-        let content = fs::read_to_string(generator[0].path())
-            .context("While reading test case generator file")?;
-
-        generate(&lang, cfg, &content, &mut config).context("While generating test cases")?;
-
-        let iter = starting_code
-            .filter_map(|item| Language::from_str(&item.ext).ok().map(|x| (item, x)))
-            .map(|(item, lang)| -> anyhow::Result<_> {
-                Ok((
-                    lang,
-                    LanguageInfo {
-                        code: fs::read_to_string(item.path()).context("Expected valid path")?,
-                        runner: runner_template(&lang, &config, &id)
-                            .context("While generating runner template")?,
-                    },
-                ))
-            });
+        config.set_seeds(&id);
+        load_fixtures(content, &id, &mut config, cfg).context("While loading fixtures")?;
 
-        let mut lang_info = HashMap::new();
-        for info in iter {
-            match info {
-                Ok((lang, info)) => {
-                    lang_info.insert(lang, info);
+        // `io` exercises have their test cases in the config. Without a
+        // generator, test cases come from the constraints in the config, with
+        // the outputs of a reference solution. Either way, they're locked in
+        // `tests.lock.json` so they only change on purpose.
+        if config.io.is_none() {
+            let lockfile = content.path.join(LOCKFILE);
+            let generator = src
+                .query_optional_file("generator", FileType::Code)
+                .context("While looking for a test case generator")?;
+            match generator {
+                Some(generator) => {
+                    let lang = Language::from_str(&generator.ext)?;
+                    let content = fs::read_to_string(generator.path())
+                        .context("While reading test case generator file")?;
+
+                    locked_test_cases(
+                        &lockfile,
+                        cfg,
+                        &config_src,
+                        &[&content],
+                        &mut config,
+                        |config| generate_cached(&lang, cfg, &content, &config_src, config),
+                    )
+                    .context("While generating test cases")?;
+                }
+                None => {
+                    let solution = src
+                        .query_optional_files("solution", FileType::Code)
+                        .filter_map(|item| Some((Language::from_str(&item.ext).ok()?, item)))
+                        // files are listed in no particular order, so the same
+                        // solution has to be picked every time
+                        .min_by(|(_, a), (_, b)| a.ext.cmp(&b.ext))
+                        .context(
+                            "Exercises without a `generator` need a `solution` to compute \
+                             the outputs of their test cases",
+                        )?;
+                    let (lang, item) = solution;
+                    let content = item
+                        .read_to_string()
+                        .context("While reading reference solution file")?;
+
+                    locked_test_cases(
+                        &lockfile,
+                        cfg,
+                        &config_src,
+                        &[&content],
+                        &mut config,
+                        |config| {
+                            generate_from_constraints(&lang, cfg, &content, &config_src, config)
+                        },
+                    )
+                    .context("While generating test cases")?;
                 }
-                Err(e) => {
-                    return Err(e.context("While parsing Language Info"));
+            }
+        }
+
+        // hand-written starter code has to match the functions in the config,
+        // and is generated for the languages that don't have any. `io`
+        // exercises have no functions to generate it from.
+        let mut starting_code = HashMap::new();
+        for item in src.query_optional_files(&id, FileType::Code) {
+            let Ok(lang) = Language::from_str(&item.ext) else {
+                continue;
+            };
+            let code = item.read_to_string()?;
+            if config.io.is_none() {
+                check_stub(&lang, &config, &code)
+                    .with_context(|| format!("Invalid starter code `{id}.{}`", item.ext))?;
+            }
+            starting_code.insert(lang, code);
+        }
+        match config.io {
+            Some(_) => anyhow::ensure!(
+                !starting_code.is_empty(),
+                "Required file(s) `{id}.{}` not found!",
+                FileType::Code
+            ),
+            None => {
+                let configured = all::<Language>()
+                    .filter(|lang| cfg.docker.language_config.contains_key(lang.image()));
+                for lang in configured {
+                    if !starting_code.contains_key(&lang) {
+                        let stub = generate_stub(&lang, &config).with_context(|| {
+                            format!("While generating starter code for {lang:?}")
+                        })?;
+                        starting_code.insert(lang, stub);
+                    }
                 }
             }
         }
 
+        let mut lang_info = HashMap::new();
+        for (lang, code) in starting_code {
+            let runner = match config.io {
+                Some(_) => String::new(),
+                None => runner_template(&lang, &config, &id)
+                    .context("While generating runner template")?,
+            };
+            lang_info.insert(lang, LanguageInfo { code, runner });
+        }
+
         config.instructions = parse_md(&instructions.read_to_string()?, context)
             .context("While parsing markdown for `instructions.md`")?;
 
-        Ok(Exercise::new(config, lang_info))
+        let exercise = Exercise::new(config, lang_info, checker);
+        // every reference solution has to pass, so the exercise is known to
+        // be solvable in its language. They're only run again when something
+        // changed.
+        for item in src.query_optional_files("solution", FileType::Code) {
+            let Ok(lang) = Language::from_str(&item.ext) else {
+                continue;
+            };
+            verify_cached(&exercise, &lang, &item.read_to_string()?, &id, cfg)
+                .with_context(|| format!("Invalid reference solution `solution.{}`", item.ext))?;
+        }
+
+        Ok(exercise)
+    }
+}
+
+/// Read the fixtures declared in the config from the `fixtures` directory
+fn load_fixtures(
+    content: &DirectoryContent,
+    id: &str,
+    config: &mut ExerciseConfig,
+    cfg: &Config,
+) -> anyhow::Result<()> {
+    if config.fixtures.is_empty() {
+        return Ok(());
+    }
+    let dir = content.get_directory("fixtures")?;
+
+    for (path, fixture) in config.fixtures.iter_mut() {
+        // the code and the harness would be overwritten
+        for lang in all::<Language>() {
+            let Some(lang_cfg) = cfg.docker.language_config.get(lang.image()) else {
+                continue;
+            };
+            anyhow::ensure!(
+                *path != lang_cfg.source_path && *path != lang.code_path(id),
+                "Fixture `{path}` has the same path as the code in {lang:?}"
+            );
+        }
+
+        fixture.content =
+            fs::read(dir.join(path)).with_context(|| format!("While reading fixture `{path}`"))?;
+        anyhow::ensure!(
+            !fixture.visible || std::str::from_utf8(&fixture.content).is_ok(),
+            "Fixture `{path}` is visible, but isn't text"
+        );
     }
+    Ok(())
 }
 
 pub fn transform(exercise: &mut Exercise) {
     for (_, cfg) in exercise.config.functions.iter_mut() {
         cfg.tests.retain(|test| !test.hidden);
     }
+    if let Some(io) = &mut exercise.config.io {
+        io.tests.retain(|test| !test.hidden);
+    }
 }
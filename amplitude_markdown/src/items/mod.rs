@@ -1,6 +1,6 @@
 use crate::items::utils::ErrorList;
 use crate::parse::context::DataContext;
-use crate::path::{DirectoryContent, FileType, FromDirectory, FromFile};
+use crate::path::{from_directory, DirectoryContent, FileType, FromDirectory, FromFile};
 use amplitude_common::config::Config;
 use anyhow::Context;
 
@@ -82,3 +82,17 @@ pub fn parse_item(
 
     anyhow::bail!(errors)
 }
+
+/// Parse an item that's a directory, which is always an exercise
+pub fn parse_directory_item(
+    path: &Path,
+    context: &mut DataContext,
+    track_id: &str,
+    cfg: &Config,
+) -> anyhow::Result<()> {
+    let exercise = from_directory::<exercise::Exercise>(path, context, cfg)
+        .context("While attempting to parse as `Exercise`")?;
+    context
+        .add_item(ItemType::Exercise(exercise), track_id)
+        .context("While adding item to context")
+}
@@ -5,6 +5,7 @@
 
 use std::ffi::OsStr;
 
+pub mod git;
 // mod item;
 pub mod items;
 /// This crate parses markdown and toml files to build rendered html course
@@ -2,18 +2,29 @@ pub mod context;
 pub mod course;
 pub mod inject;
 pub mod link_concat;
+pub mod requires;
 
-use crate::{items::ItemType, parse::course::parse_course, OsStrToString};
-use amplitude_common::config::{Config, ParseConfig};
+use crate::{
+    git::sync_repo,
+    items::ItemType,
+    parse::course::{parse_course, parse_course_item},
+    OsStrToString,
+};
+use amplitude_common::config::Config;
 use anyhow::Context;
 use comrak::{
     nodes::AstNode, parse_document_refs, Arena, ComrakExtensionOptions, ComrakOptions,
     ComrakRenderOptions, ListStyleType, RefMap,
 };
-use git2::build::RepoBuilder;
 use link_concat::link_concat_callback;
 use serde::{ser::SerializeMap, Serialize, Serializer};
-use std::{collections::HashMap, default::default, fs, path::Path, vec};
+use std::{
+    collections::{BTreeMap, HashMap},
+    default::default,
+    fs,
+    path::Path,
+    vec,
+};
 use tracing::{info, warn};
 
 use self::{
@@ -22,32 +33,15 @@ use self::{
     inject::InjectData,
 };
 
-/// Clones the articles repo
-pub fn clone_repo(config: &ParseConfig) -> anyhow::Result<()> {
-    let clone_path = &config.clone_path;
-    fs::create_dir_all(clone_path)?;
-
-    // delete everything
-    for item in fs::read_dir(clone_path)? {
-        let item = item?;
-        let path = item.path();
-        if path.is_dir() {
-            fs::remove_dir_all(path)?;
-        } else {
-            fs::remove_file(path)?;
-        }
-    }
-
-    RepoBuilder::new().clone(&config.git_url, Path::new(clone_path))?;
-
-    Ok(())
-}
-
 /// Reparses the things and does the things
 pub fn parse(config: &Config) -> anyhow::Result<ParseData> {
     if !config.args.local {
-        info!("Deleting `{}` and recloning repo... (If you dont want this behavior, run with `--local`)", config.parse.clone_path);
-        clone_repo(&config.parse).context("While cloning repo")?;
+        info!(
+            "Updating repo in `{}`... (If you dont want this behavior, run with `--local`)",
+            config.parse.clone_path
+        );
+        let commit = sync_repo(&config.parse).context("While updating repo")?;
+        info!("Using commit `{commit}` for articles");
     } else {
         info!(
             "Using local files in `{}` for articles",
@@ -55,6 +49,37 @@ pub fn parse(config: &Config) -> anyhow::Result<ParseData> {
         );
     }
 
+    let data = parse_courses(config)?;
+
+    dbg!(&data);
+
+    Ok(data)
+}
+
+/// Parses every course in `clone_path`, without cloning it first
+pub fn parse_courses(config: &Config) -> anyhow::Result<ParseData> {
+    info!("Parsing articles...");
+
+    let mut data =
+        RawCourseData::new(markdown_context()).context("While creating `RawCourseData`")?;
+    for item in fs::read_dir(&config.parse.clone_path)? {
+        let item = item?;
+        let path = item.path();
+        if path.is_dir() {
+            let name = path.file_name().to_string();
+            if name.starts_with('.') {
+                continue;
+            }
+
+            parse_course(path, &mut data, config)
+                .with_context(|| format!("While parsing course `{name}`"))?;
+        }
+    }
+    ParseData::from_raw(data).context("While generating `ParseData`")
+}
+
+/// The options and refs markdown is parsed with, before a course sets its refs
+fn markdown_context() -> MarkdownContext {
     let options = ComrakOptions {
         extension: ComrakExtensionOptions {
             strikethrough: true,
@@ -80,32 +105,32 @@ pub fn parse(config: &Config) -> anyhow::Result<ParseData> {
             sourcepos: false,
         },
     };
-    let md_ctx = MarkdownContext {
+    MarkdownContext {
         options,
         refs: RefMap::new(),
-    };
-
-    info!("Parsing articles...");
-
-    let mut data = RawCourseData::new(md_ctx).context("While creating `RawCourseData`")?;
-    for item in fs::read_dir(&config.parse.clone_path)? {
-        let item = item?;
-        let path = item.path();
-        if path.is_dir() {
-            let name = path.file_name().to_string();
-            if name.starts_with('.') {
-                continue;
-            }
-
-            parse_course(path, &mut data, config)
-                .with_context(|| format!("While parsing course `{name}`"))?;
-        }
     }
-    let data = ParseData::from_raw(data).context("While generating `ParseData`")?;
+}
 
-    dbg!(&data);
+/// Parse just the course at `path`, to replace it in the full `ParseData`
+/// with [`ParseData::replace_course`]
+pub fn parse_one_course(config: &Config, path: &Path) -> anyhow::Result<ParseData> {
+    let mut data = RawCourseData::new(markdown_context())?;
+    parse_course(path.to_path_buf(), &mut data, config)?;
+    ParseData::from_raw(data)
+}
 
-    Ok(data)
+/// Parse just the item at `path`, from the track at `track_path` in the course
+/// at `course_path`, to replace it in the full `ParseData` with
+/// [`ParseData::replace_item`]
+pub fn parse_one_item(
+    config: &Config,
+    course_path: &Path,
+    track_path: &Path,
+    path: &Path,
+) -> anyhow::Result<ParseData> {
+    let mut data = RawCourseData::new(markdown_context())?;
+    parse_course_item(course_path, track_path, path, &mut data, config)?;
+    ParseData::from_raw(data)
 }
 
 fn parse_into_ast<'a>(
@@ -204,7 +229,13 @@ fn as_hashmap<K: Serialize, V: Serialize, S: Serializer>(
 pub enum TreeItem {
     #[serde(serialize_with = "as_hashmap")]
     Course(Vec<(String, TreeItem)>),
-    Track(Vec<String>),
+    Track {
+        items: Vec<String>,
+        /// Ids of the tracks to do before this one
+        requires: Vec<String>,
+        /// Ids of the items to do before each item that has prerequisites
+        item_requires: BTreeMap<String, Vec<String>>,
+    },
 }
 
 impl ParseData {
@@ -216,7 +247,14 @@ impl ParseData {
                 TreeItem::Course(
                     tracks
                         .iter()
-                        .map(|t| (t.id.clone(), TreeItem::Track(t.items.clone())))
+                        .map(|t| {
+                            let track = TreeItem::Track {
+                                items: t.items.clone(),
+                                requires: t.requires.clone(),
+                                item_requires: t.item_requires.clone(),
+                            };
+                            (t.id.clone(), track)
+                        })
                         .collect(),
                 ),
             );
@@ -229,6 +267,33 @@ impl ParseData {
             tree: courses,
         })
     }
+
+    /// Replace everything from the course `id` with the course in `new`, which
+    /// was parsed on its own. The course is removed if `new` is `None`.
+    pub fn replace_course(&mut self, id: &str, new: Option<ParseData>) {
+        let index = format!("{id}-index");
+        let prefix = format!("{id}/");
+        self.course_data.remove(id);
+        self.tracks.remove(id);
+        self.tree.remove(id);
+        self.items
+            .retain(|k, _| k != &index && !k.starts_with(&prefix));
+
+        if let Some(new) = new {
+            self.course_data.extend(new.course_data);
+            self.tracks.extend(new.tracks);
+            self.tree.extend(new.tree);
+            self.items.extend(new.items);
+        }
+    }
+
+    /// Replace the item `id`, and the items inside of it like quizzes, with
+    /// the ones in `new`, which was parsed on its own
+    pub fn replace_item(&mut self, id: &str, new: ParseData) {
+        let prefix = format!("{id}/");
+        self.items.retain(|k, _| k != id && !k.starts_with(&prefix));
+        self.items.extend(new.items);
+    }
 }
 
 impl RawCourseData {
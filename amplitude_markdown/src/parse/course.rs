@@ -2,10 +2,12 @@ use super::*;
 
 use crate::items::{
     article::{Article, RawArticle},
-    parse_item,
+    parse_directory_item, parse_item,
 };
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::{collections::BTreeMap, path::PathBuf};
+
+use super::requires::check_dag;
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(deny_unknown_fields)]
@@ -19,6 +21,25 @@ pub struct CourseConfig {
 pub struct RawTrack {
     pub title: String,
     pub description: String,
+    /// Ids of items in the order they come in, before the ones that aren't
+    /// listed, which are ordered by the number their name starts with
+    #[serde(default)]
+    pub order: Vec<String>,
+    /// Ids of tracks to do before this one
+    #[serde(default)]
+    pub requires: Vec<String>,
+    #[serde(default)]
+    pub items: HashMap<String, RawTrackItem>,
+}
+
+/// What `track.toml` says about one of its items, under `[items.<id>]`
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RawTrackItem {
+    /// Ids of items to do before this one, which can be in any track of the
+    /// course
+    #[serde(default)]
+    pub requires: Vec<String>,
 }
 
 #[derive(Serialize, Debug)]
@@ -27,6 +48,10 @@ pub struct Track {
     pub title: String,
     pub description: String,
     pub items: Vec<String>,
+    /// Ids of the tracks to do before this one
+    pub requires: Vec<String>,
+    /// The items of this track that require others, by their full ids
+    pub item_requires: BTreeMap<String, Vec<String>>,
 }
 
 impl Track {
@@ -36,18 +61,14 @@ impl Track {
             title: raw.title,
             description: raw.description,
             items: Vec::new(),
+            requires: raw.requires,
+            item_requires: BTreeMap::new(),
         })
     }
 }
 
 pub fn parse_course(path: PathBuf, data: &mut RawCourseData, cfg: &Config) -> anyhow::Result<()> {
-    let arena = Arena::new();
-    let refs = {
-        let header = fs::read_to_string(path.join("header.md"))?;
-        Some(parse_document_refs(&arena, &header))
-    }
-    .unwrap_or(RefMap::new());
-    data.markdown_context.refs = refs;
+    data.markdown_context.refs = header_refs(&path)?;
 
     let course_id = path.file_name().to_string();
 
@@ -71,6 +92,7 @@ pub fn parse_course(path: PathBuf, data: &mut RawCourseData, cfg: &Config) -> an
     data.items
         .insert(course_id.clone() + "-index", ItemType::Article(index));
 
+    let mut tracks = Vec::new();
     for dir in fs::read_dir(&path)? {
         let dir = dir?;
         let path = dir.path();
@@ -90,33 +112,118 @@ pub fn parse_course(path: PathBuf, data: &mut RawCourseData, cfg: &Config) -> an
             continue;
         }
 
+        tracks.push(path);
+    }
+    tracks.sort_by_key(|path| order_key(path));
+
+    for path in tracks {
+        let track_id = path.file_name().to_string();
         let mut ctx = DataContext::new(data, &course_id)?;
 
         parse_track(path, &mut ctx, cfg)
             .with_context(|| format!("While parsing track {track_id}"))?;
     }
 
-    Ok(())
+    check_requires(&data.tracks[&course_id])
+}
+
+/// The link refs in the `header.md` of the course at `path`, which every item
+/// in the course can use
+fn header_refs(path: &Path) -> anyhow::Result<RefMap> {
+    let header = fs::read_to_string(path.join("header.md"))?;
+    Ok(parse_document_refs(&Arena::new(), &header))
+}
+
+/// Parse just the item at `path`, from the track at `track_path` in the course
+/// at `course_path`. The track is added without its other items.
+pub fn parse_course_item(
+    course_path: &Path,
+    track_path: &Path,
+    path: &Path,
+    data: &mut RawCourseData,
+    cfg: &Config,
+) -> anyhow::Result<()> {
+    data.markdown_context.refs = header_refs(course_path)?;
+    let course_id = course_path.file_name().to_string();
+
+    let track: RawTrack = toml::from_str(&fs::read_to_string(track_path.join("track.toml"))?)
+        .context("While parsing `track.toml`")?;
+    let track_id = strip_prefix(track_path);
+    let track = Track::from_raw(track, track_id.clone())?;
+    data.tracks.insert(course_id.clone(), vec![track]);
+
+    let mut ctx = DataContext::new(data, &course_id)?;
+    ctx.scope(&item_id(path), |ctx| {
+        parse_track_item(path, ctx, &track_id, cfg)
+    })
+}
+
+/// Split the number off the start of a name like `01_intro.md`, returning it
+/// and the rest of the name. Names without one are `None`.
+fn split_prefix(path: &Path) -> (Option<u32>, String) {
+    let name = path.file_name().to_string();
+    match name.split_once('_') {
+        Some((number, rest))
+            if !number.is_empty() && number.bytes().all(|b| b.is_ascii_digit()) =>
+        {
+            (number.parse().ok(), rest.to_string())
+        }
+        _ => (None, name),
+    }
 }
 
 fn strip_prefix(path: &Path) -> String {
-    path.file_name().unwrap().to_str().unwrap()[3..].to_string()
+    split_prefix(path).1
+}
+
+/// Ordered by their number, then by name, with the ones without a number last
+fn order_key(path: &Path) -> (u32, String) {
+    let (number, name) = split_prefix(path);
+    (number.unwrap_or(u32::MAX), name)
+}
+
+/// Check that the prerequisites of the tracks of a course and their items
+/// exist, and don't have cycles
+fn check_requires(tracks: &[Track]) -> anyhow::Result<()> {
+    let track_graph = tracks
+        .iter()
+        .map(|track| (track.id.clone(), track.requires.clone()))
+        .collect();
+    let item_graph = tracks
+        .iter()
+        .flat_map(|track| {
+            track.items.iter().map(|id| {
+                let requires = track.item_requires.get(id).cloned().unwrap_or_default();
+                (id.clone(), requires)
+            })
+        })
+        .collect();
+
+    let errors = [
+        check_dag("track", &track_graph),
+        check_dag("item", &item_graph),
+    ]
+    .concat();
+    anyhow::ensure!(
+        errors.is_empty(),
+        "Invalid prerequisites:\n{}",
+        errors.join("\n")
+    );
+    Ok(())
 }
 
 pub fn parse_track(path: PathBuf, ctx: &mut DataContext, cfg: &Config) -> anyhow::Result<()> {
-    let track: RawTrack = toml::from_str(&fs::read_to_string(path.join("track.toml"))?)
+    let mut track: RawTrack = toml::from_str(&fs::read_to_string(path.join("track.toml"))?)
         .context("While parsing `track.toml`")?;
     let track_id = strip_prefix(&path);
-    let track = Track::from_raw(track, track_id.clone())?;
-
-    ctx.add_track(track)?;
+    let order = std::mem::take(&mut track.order);
+    let item_configs = std::mem::take(&mut track.items);
+    let mut track = Track::from_raw(track, track_id.clone())?;
 
+    let mut items = Vec::new();
     for item in fs::read_dir(&path)? {
         let item = item?;
         let path = item.path();
-        if path.is_dir() {
-            continue;
-        }
 
         let file_name = item.file_name();
         let file_name = file_name.to_str().unwrap();
@@ -127,16 +234,61 @@ pub fn parse_track(path: PathBuf, ctx: &mut DataContext, cfg: &Config) -> anyhow
             continue;
         }
 
-        let id = strip_prefix(&path)
-            .split_once('.')
-            .map(|x| x.0.to_string())
-            .unwrap_or_else(|| strip_prefix(&path));
+        items.push((item_id(&path), path));
+    }
 
-        ctx.scope(&id, |ctx| {
-            parse_item(&path, ctx, &track_id, cfg)
-                .with_context(|| format!("While parsing item at path `{}`", path.to_string_lossy()))
-        })?
+    items.sort_by_key(|(_, path)| order_key(path));
+    for id in order.iter().chain(item_configs.keys()) {
+        anyhow::ensure!(
+            items.iter().any(|(x, _)| x == id),
+            "`{id}` in `track.toml` is not an item of the track"
+        );
+    }
+    items.sort_by_key(|(id, _)| order.iter().position(|x| x == id).unwrap_or(order.len()));
+
+    let full_id = |id: &str| format!("{}/{id}", ctx.id());
+    track.item_requires = item_configs
+        .into_iter()
+        .filter(|(_, item)| !item.requires.is_empty())
+        .map(|(id, item)| {
+            (
+                full_id(&id),
+                item.requires.iter().map(|x| full_id(x)).collect(),
+            )
+        })
+        .collect();
+    ctx.add_track(track)?;
+
+    for (id, path) in items {
+        ctx.scope(&id, |ctx| parse_track_item(&path, ctx, &track_id, cfg))?
     }
 
     Ok(())
 }
+
+/// The id of the item at `path` in a track, its name without the number and
+/// the extension
+pub fn item_id(path: &Path) -> String {
+    if path.is_dir() {
+        return strip_prefix(path);
+    }
+    strip_prefix(path)
+        .split_once('.')
+        .map(|x| x.0.to_string())
+        .unwrap_or_else(|| strip_prefix(path))
+}
+
+/// Directories are exercises, files are articles and quizzes
+fn parse_track_item(
+    path: &Path,
+    ctx: &mut DataContext,
+    track_id: &str,
+    cfg: &Config,
+) -> anyhow::Result<()> {
+    if path.is_dir() {
+        parse_directory_item(path, ctx, track_id, cfg)
+    } else {
+        parse_item(path, ctx, track_id, cfg)
+    }
+    .with_context(|| format!("While parsing item at path `{}`", path.to_string_lossy()))
+}
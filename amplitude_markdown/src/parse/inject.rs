@@ -9,7 +9,9 @@ use serde::Serialize;
 
 mod admonition;
 mod code;
+mod details;
 mod quiz;
+mod tabs;
 mod utils;
 
 type CallbackArgs = HashMap<String, String>;
@@ -70,7 +72,13 @@ impl<T: Callback> DynCallback for T {
     }
 }
 
-const CALLBACKS: &[&'static dyn DynCallback] = &[&admonition::Admonition, &quiz::Quiz, &code::Code];
+const CALLBACKS: &[&'static dyn DynCallback] = &[
+    &admonition::Admonition,
+    &quiz::Quiz,
+    &code::Code,
+    &tabs::Tabs,
+    &details::Details,
+];
 #[ctor::ctor]
 static MARKERS: HashMap<&'static str, &'static dyn DynCallback> = {
     let mut tags = HashMap::new();
@@ -86,6 +94,8 @@ static MARKERS: HashMap<&'static str, &'static dyn DynCallback> = {
 pub enum ExpectedTag {
     CodeBlock(Option<&'static str>),
     BlockQuote,
+    /// Any of these
+    OneOf(&'static [ExpectedTag]),
 }
 
 impl ExpectedTag {
@@ -106,6 +116,7 @@ impl ExpectedTag {
             },
             CodeBlock(None) => matches!(val, NodeValue::CodeBlock(_)),
             BlockQuote => matches!(val, NodeValue::BlockQuote),
+            OneOf(tags) => tags.iter().any(|tag| tag.matches(node)),
         }
     }
 }
@@ -307,3 +318,48 @@ pub fn test_parse_args() {
         ],
     );
 }
+
+#[cfg(test)]
+fn parse_test_md(input: &str) -> anyhow::Result<String> {
+    use crate::parse::{markdown_context, parse_md, RawCourseData};
+
+    let mut data = RawCourseData::new(markdown_context())?;
+    let mut ctx = DataContext::new(&mut data, "course/article")?;
+    parse_md(input, &mut ctx)
+}
+
+#[test]
+pub fn test_code_tabs() {
+    let html =
+        parse_test_md("@tabs\n\n```python\nx = 1\n```\n\n```rust\nlet x = 1;\n```\n\nafter\n")
+            .unwrap();
+    assert!(html.starts_with("<Tabs><div class=\"tab\" data-title=\"python\">"));
+    assert!(html.contains("</div><div class=\"tab\" data-title=\"rust\">"));
+    // the blocks after the first are merged into it, not left after the tabs
+    assert_eq!(html.matches("let x = 1;").count(), 1);
+    assert!(html.ends_with("</div></Tabs>\n<p>after</p>\n"));
+}
+
+#[test]
+pub fn test_tabs_need_heading() {
+    let err = parse_test_md("@tabs\n\n> text\n>\n> # Python\n>\n> more\n").unwrap_err();
+    assert_eq!(
+        err.root_cause().to_string(),
+        "Blockquotes in `@tabs` have to start with a heading to title the first tab"
+    );
+}
+
+#[test]
+pub fn test_details_need_content() {
+    let err = parse_test_md("@details\n\n> Summary\n").unwrap_err();
+    assert_eq!(
+        err.root_cause().to_string(),
+        "`@details` must be given a summary and something to show"
+    );
+
+    let html = parse_test_md("@details open\n\n> Summary\n>\n> Content\n").unwrap();
+    assert_eq!(
+        html,
+        "<Collapsible summary=\"Summary\" open=\"true\"><p>Content</p>\n</Collapsible>\n"
+    );
+}
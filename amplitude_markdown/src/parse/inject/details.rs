@@ -0,0 +1,52 @@
+use super::{utils::escape_attr, *};
+
+pub struct Details;
+
+impl Callback for Details {
+    fn run_callback<'a>(
+        &self,
+        args: CallbackArgs,
+        node: &'a AstNode<'a>,
+        ctx: &mut DataContext,
+    ) -> CallbackRet<'a> {
+        let children = node.children().collect::<Vec<_>>();
+        anyhow::ensure!(
+            children.len() > 1,
+            "`@details` must be given a summary and something to show"
+        );
+        let summary = children[0];
+        anyhow::ensure!(
+            matches!(summary.data.borrow().value, NodeValue::Paragraph),
+            "the summary of `@details` must be its first paragraph"
+        );
+
+        let summary = parse_ast(summary, ctx.markdown_context())
+            .context("failed to parse details summary into valid string")?;
+        let summary = summary
+            .strip_prefix("<p>")
+            .and_then(|s| s.strip_suffix("</p>\n"))
+            .context("expected paragraph tags in html")?;
+        let mut html = String::new();
+        for child in &children[1..] {
+            html += &parse_ast(child, ctx.markdown_context())
+                .context("failed to parse details output into valid string")?;
+        }
+
+        let summary = escape_attr(summary);
+        let open = if args.contains_key("open") {
+            " open=\"true\""
+        } else {
+            ""
+        };
+        // `<details>` is already an html element, which the component renders
+        node.data.borrow_mut().value = NodeValue::HtmlInline(format!(
+            "<Collapsible summary=\"{summary}\"{open}>{html}</Collapsible>\n"
+        ));
+
+        Ok(children)
+    }
+
+    const MARKER: &'static str = "@details";
+    const EXPECTED_TAG: ExpectedTag = ExpectedTag::BlockQuote;
+    const OPTIONAL_KEYS: &'static [&'static str] = &["open"];
+}
@@ -0,0 +1,104 @@
+use std::iter;
+
+use super::{utils::escape_attr, *};
+
+pub struct Tabs;
+
+/// The title and html of each tab
+type TabList = Vec<(String, String)>;
+
+impl Callback for Tabs {
+    fn run_callback<'a>(
+        &self,
+        _: CallbackArgs,
+        node: &'a AstNode<'a>,
+        ctx: &mut DataContext,
+    ) -> CallbackRet<'a> {
+        let is_code = matches!(node.data.borrow().value, NodeValue::CodeBlock(_));
+        let (tabs, to_detach) = if is_code {
+            code_tabs(node, ctx)?
+        } else {
+            section_tabs(node, ctx)?
+        };
+
+        let tabs = tabs
+            .iter()
+            .map(|(title, html)| {
+                let title = escape_attr(title);
+                format!("<div class=\"tab\" data-title=\"{title}\">{html}</div>")
+            })
+            .collect::<String>();
+        node.data.borrow_mut().value = NodeValue::HtmlInline(format!("<Tabs>{tabs}</Tabs>\n"));
+
+        Ok(to_detach)
+    }
+
+    const MARKER: &'static str = "@tabs";
+    const EXPECTED_TAG: ExpectedTag =
+        ExpectedTag::OneOf(&[ExpectedTag::CodeBlock(None), ExpectedTag::BlockQuote]);
+}
+
+/// A tab for each code block in a row starting at `node`, titled by its
+/// language. Returns the code blocks after the first, which are merged into it.
+fn code_tabs<'a>(
+    node: &'a AstNode<'a>,
+    ctx: &mut DataContext,
+) -> anyhow::Result<(TabList, Vec<&'a AstNode<'a>>)> {
+    let blocks = iter::successors(Some(node), |n| n.next_sibling())
+        .take_while(|n| matches!(n.data.borrow().value, NodeValue::CodeBlock(_)))
+        .collect::<Vec<_>>();
+
+    let mut tabs = Vec::new();
+    for block in &blocks {
+        let lang = match &block.data.borrow().value {
+            NodeValue::CodeBlock(code) => code.info.split_whitespace().next().map(str::to_string),
+            _ => unreachable!(),
+        }
+        .context("Code blocks in `@tabs` need a language to title their tab")?;
+        let html = parse_ast(block, ctx.markdown_context())
+            .context("failed to parse tab output into valid string")?;
+        tabs.push((lang, html));
+    }
+
+    Ok((tabs, blocks[1..].to_vec()))
+}
+
+/// A tab for each heading in the blockquote `node`, with everything up to the
+/// next heading of the same level. Returns the blockquote's children.
+fn section_tabs<'a>(
+    node: &'a AstNode<'a>,
+    ctx: &mut DataContext,
+) -> anyhow::Result<(TabList, Vec<&'a AstNode<'a>>)> {
+    let level = |n: &AstNode| match n.data.borrow().value {
+        NodeValue::Heading(ref heading) => Some(heading.level),
+        _ => None,
+    };
+    let children = node.children().collect::<Vec<_>>();
+    let first = children
+        .first()
+        .and_then(|n| level(n))
+        .context("Blockquotes in `@tabs` have to start with a heading to title the first tab")?;
+
+    let mut tabs: TabList = Vec::new();
+    for child in &children {
+        if level(child) == Some(first) {
+            tabs.push((text(child), String::new()));
+            continue;
+        }
+        let html = parse_ast(child, ctx.markdown_context())
+            .context("failed to parse tab output into valid string")?;
+        tabs.last_mut().unwrap().1 += &html;
+    }
+
+    Ok((tabs, children))
+}
+
+/// The text in `node`, without its formatting
+fn text<'a>(node: &'a AstNode<'a>) -> String {
+    node.descendants()
+        .filter_map(|n| match &n.data.borrow().value {
+            NodeValue::Text(text) => Some(text.clone()),
+            _ => None,
+        })
+        .collect()
+}
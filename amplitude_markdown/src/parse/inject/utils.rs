@@ -1 +1,7 @@
-
+/// Escape `s` to be put in a double quoted html attribute
+pub fn escape_attr(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
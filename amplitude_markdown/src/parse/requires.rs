@@ -0,0 +1,110 @@
+//! Items and tracks can require others to be done first. The prerequisites
+//! have to form a DAG, which is checked once a course is parsed.
+
+use std::collections::{BTreeMap, HashMap};
+
+#[derive(Clone, Copy, PartialEq)]
+enum Visit {
+    InProgress,
+    Done,
+}
+
+/// Check that everything in `graph`, from an id to the ids it requires, only
+/// requires ids that exist and that there are no cycles. Returns a line for
+/// each problem, `kind` is what the ids are called in them.
+pub fn check_dag(kind: &str, graph: &BTreeMap<String, Vec<String>>) -> Vec<String> {
+    let mut errors = Vec::new();
+    for (id, requires) in graph {
+        for req in requires.iter().filter(|req| !graph.contains_key(*req)) {
+            errors.push(format!(
+                "`{id}` requires `{req}`, which isn't a known {kind}"
+            ));
+        }
+    }
+
+    let mut visits = HashMap::new();
+    let mut cycles = Vec::new();
+    for id in graph.keys() {
+        visit(id, graph, &mut visits, &mut Vec::new(), &mut cycles);
+    }
+    for cycle in cycles {
+        let cycle = cycle
+            .iter()
+            .map(|id| format!("`{id}`"))
+            .collect::<Vec<_>>()
+            .join(" -> ");
+        errors.push(format!("Cycle of {kind} prerequisites: {cycle}"));
+    }
+
+    errors
+}
+
+/// Depth first search from `id`, pushing the path to every id that's already
+/// on the `stack` to `cycles`
+fn visit<'a>(
+    id: &'a str,
+    graph: &'a BTreeMap<String, Vec<String>>,
+    visits: &mut HashMap<&'a str, Visit>,
+    stack: &mut Vec<&'a str>,
+    cycles: &mut Vec<Vec<&'a str>>,
+) {
+    match visits.get(id) {
+        Some(Visit::Done) => return,
+        Some(Visit::InProgress) => {
+            let start = stack.iter().position(|x| *x == id).unwrap();
+            let mut cycle = stack[start..].to_vec();
+            cycle.push(id);
+            cycles.push(cycle);
+            return;
+        }
+        None => {}
+    }
+    // missing ids are already reported
+    let Some(requires) = graph.get(id) else {
+        return;
+    };
+
+    visits.insert(id, Visit::InProgress);
+    stack.push(id);
+    for req in requires {
+        visit(req, graph, visits, stack, cycles);
+    }
+    stack.pop();
+    visits.insert(id, Visit::Done);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn graph(edges: &[(&str, &[&str])]) -> BTreeMap<String, Vec<String>> {
+        edges
+            .iter()
+            .map(|(id, requires)| {
+                let requires = requires.iter().map(|x| x.to_string()).collect();
+                (id.to_string(), requires)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_check_dag() {
+        let dag = graph(&[("a", &[]), ("b", &["a"]), ("c", &["a", "b"])]);
+        assert!(check_dag("item", &dag).is_empty());
+
+        let broken = graph(&[
+            ("a", &["c"]),
+            ("b", &["a", "x"]),
+            ("c", &["b"]),
+            ("d", &["d"]),
+        ]);
+        assert_eq!(
+            check_dag("item", &broken),
+            [
+                "`b` requires `x`, which isn't a known item",
+                "Cycle of item prerequisites: `a` -> `c` -> `b` -> `a`",
+                "Cycle of item prerequisites: `d` -> `d`",
+            ]
+        );
+    }
+}
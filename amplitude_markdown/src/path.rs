@@ -102,6 +102,29 @@ impl<'a> DirectoryContent<'a> {
         Ok(iter.next().unwrap())
     }
 
+    /// Get a file by name and filetype if it exists, erroring if there are
+    /// multiple that match the query.
+    pub fn query_optional_file(
+        &'a self,
+        name: &'a str,
+        typ: FileType,
+    ) -> anyhow::Result<Option<&'a DirItem>> {
+        let mut iter = self
+            .files
+            .iter()
+            .filter(move |item| item.item_type == typ)
+            .filter(move |item| item.name == name);
+        let item = iter.next();
+        if iter.next().is_some() {
+            anyhow::bail!(
+                "Multiple files `{}.{}` found! Expected only one...",
+                name,
+                typ
+            );
+        }
+        Ok(item)
+    }
+
     /// Get files by name and filetype, erroring if they don't exist.
     pub fn query_files(
         &'a self,
@@ -120,6 +143,18 @@ impl<'a> DirectoryContent<'a> {
         Ok(iter)
     }
 
+    /// Get files by name and filetype, which might not exist.
+    pub fn query_optional_files(
+        &'a self,
+        name: &'a str,
+        typ: FileType,
+    ) -> impl Iterator<Item = &'a DirItem> {
+        self.files
+            .iter()
+            .filter(move |item| item.item_type == typ)
+            .filter(move |item| item.name == name)
+    }
+
     pub fn query_filetype(&self, typ: FileType) -> anyhow::Result<impl Iterator<Item = &DirItem>> {
         let mut iter = self
             .files
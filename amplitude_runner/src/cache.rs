@@ -0,0 +1,317 @@
+//! Generated test cases are cached on disk, keyed by a hash of everything that
+//! goes into generating them, so exercises that haven't changed don't start a
+//! container to run their generator every time the content is parsed. The same
+//! goes for checking reference solutions.
+
+use std::{collections::HashMap, fmt::Write as _, fs, io::Write as _, path::Path};
+
+use amplitude_common::{config::Config, path};
+use anyhow::Context;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    exercise::{
+        generator_template, run_generator, set_test_cases, Exercise, ExerciseConfig, TestCase,
+    },
+    images::context_hash,
+    lang::Language,
+};
+
+/// Bumped when the format of the cached files changes
+const VERSION: &str = "1";
+
+/// Like [`generate`](crate::exercise::generate), but the test cases are taken
+/// from the cache if the generator, `config.toml` (`config_src`), language
+/// image and seeds are the same as the last time they were generated.
+pub fn generate_cached(
+    lang: &Language,
+    cfg: &Config,
+    content: &str,
+    config_src: &str,
+    exercise_cfg: &mut ExerciseConfig,
+) -> anyhow::Result<()> {
+    let program =
+        generator_template(lang, content, exercise_cfg).context("While generating template")?;
+    load_or_generate(
+        lang,
+        cfg,
+        config_src,
+        &[&program],
+        exercise_cfg,
+        |exercise_cfg| run_generator(lang, cfg, &program, exercise_cfg),
+    )
+}
+
+/// Like [`Exercise::verify_solution`], but a solution that already passed is
+/// only run again when it, the exercise's test cases, harness or checker, the
+/// language image or the limits it ran with changed.
+pub fn verify_cached(
+    exercise: &Exercise,
+    lang: &Language,
+    content: &str,
+    id: &str,
+    cfg: &Config,
+) -> anyhow::Result<()> {
+    let image = cfg
+        .docker
+        .language_config
+        .get(lang.image())
+        .map(|x| x.image_name.as_str())
+        .unwrap_or_default();
+    let context = context_hash(lang.image()).unwrap_or_default();
+    let runner = exercise
+        .lang_info
+        .get(lang)
+        .map(|x| x.runner.as_str())
+        .unwrap_or_default();
+    let checker = exercise
+        .checker
+        .as_ref()
+        .map(|x| format!("{:?}\n{}", x.lang, x.code))
+        .unwrap_or_default();
+    let limits = format!(
+        "{} {} {}",
+        cfg.docker.timeout, cfg.docker.output.run, cfg.docker.output.test
+    );
+    // through a `Value` so the keys of the maps are sorted
+    let config = serde_json::to_vec(&serde_json::to_value(&exercise.config)?)?;
+
+    let key = hash(
+        [
+            VERSION.as_bytes(),
+            b"verify",
+            image.as_bytes(),
+            context.as_bytes(),
+            content.as_bytes(),
+            runner.as_bytes(),
+            checker.as_bytes(),
+            limits.as_bytes(),
+            &config,
+        ]
+        .into_iter()
+        .chain(fixture_parts(&exercise.config)),
+    );
+    let file = path::CACHE.join(format!("verified-{key}"));
+    if file.exists() {
+        return Ok(());
+    }
+
+    exercise.verify_solution(lang, content, id, cfg)?;
+    // not being able to write the cache only makes the next parse slower
+    if let Err(e) = fs::create_dir_all(file.parent().unwrap()).and_then(|_| fs::write(&file, "")) {
+        eprintln!("Error caching verification in `{}`: {e:?}", file.display());
+    }
+    Ok(())
+}
+
+/// Set the test cases of `exercise_cfg` from the cache, or from `generate` if
+/// they aren't cached yet. `programs` are the programs run by `generate`,
+/// which gets `exercise_cfg` to know what to generate.
+pub(crate) fn load_or_generate(
+    lang: &Language,
+    cfg: &Config,
+    config_src: &str,
+    programs: &[&str],
+    exercise_cfg: &mut ExerciseConfig,
+    generate: impl FnOnce(&ExerciseConfig) -> anyhow::Result<HashMap<String, Vec<TestCase>>>,
+) -> anyhow::Result<()> {
+    let image = &cfg
+        .docker
+        .language_config
+        .get(lang.image())
+        .unwrap()
+        .image_name;
+    let key = cache_key(lang, image, config_src, programs, exercise_cfg);
+    let file = path::CACHE.join(format!("{key}.json"));
+
+    // a cache file that can't be read is treated like a miss and overwritten.
+    // `--check-locks` looks for generators that drifted, which the cache would
+    // hide.
+    if let Some(test_cases) = fs::read(&file)
+        .ok()
+        .filter(|_| !cfg.args.check_locks)
+        .and_then(|x| serde_json::from_slice(&x).ok())
+    {
+        return set_test_cases(exercise_cfg, test_cases);
+    }
+
+    let test_cases = generate(exercise_cfg)?;
+    // not being able to write the cache only makes the next parse slower
+    if let Err(e) = store(&file, &test_cases) {
+        eprintln!("Error caching test cases in `{}`: {e:?}", file.display());
+    }
+    set_test_cases(exercise_cfg, test_cases)
+}
+
+/// The hex encoded SHA-256 of everything that affects the generated test
+/// cases. `programs` are the rendered programs, so changes to the generator's
+/// source and to the language's templates are included, and so are the
+/// fixtures the programs can read.
+fn cache_key(
+    lang: &Language,
+    image: &str,
+    config_src: &str,
+    programs: &[&str],
+    exercise_cfg: &ExerciseConfig,
+) -> String {
+    // what's in the image, since the image name stays the same when it's rebuilt
+    let context = context_hash(lang.image()).unwrap_or_default();
+
+    let mut seeds = exercise_cfg
+        .functions
+        .iter()
+        .map(|(name, func)| format!("{name}:{}", func.seed))
+        .collect::<Vec<_>>();
+    seeds.sort();
+    let seeds = seeds.join(",");
+
+    hash(
+        [
+            VERSION.as_bytes(),
+            image.as_bytes(),
+            context.as_bytes(),
+            config_src.as_bytes(),
+        ]
+        .into_iter()
+        .chain(programs.iter().map(|x| x.as_bytes()))
+        .chain([seeds.as_bytes()])
+        .chain(fixture_parts(exercise_cfg)),
+    )
+}
+
+/// The path and content of every fixture, to hash them
+pub(crate) fn fixture_parts(exercise_cfg: &ExerciseConfig) -> impl Iterator<Item = &[u8]> {
+    exercise_cfg
+        .fixtures
+        .iter()
+        .flat_map(|(path, fixture)| [path.as_bytes(), fixture.content.as_slice()])
+}
+
+/// The hex encoded SHA-256 of some byte strings. Each one is length
+/// prefixed, so moving bytes from one to the next changes the hash.
+pub(crate) fn hash<'a>(parts: impl IntoIterator<Item = &'a [u8]>) -> String {
+    let mut hasher = Sha256::new();
+    for part in parts {
+        hasher.update((part.len() as u64).to_le_bytes());
+        hasher.update(part);
+    }
+
+    hasher.finalize().iter().fold(String::new(), |mut out, x| {
+        let _ = write!(out, "{x:02x}");
+        out
+    })
+}
+
+/// Write the cache file through a temp file, so a parse running at the same
+/// time never reads half of it
+fn store(file: &Path, test_cases: &HashMap<String, Vec<TestCase>>) -> anyhow::Result<()> {
+    let dir = file.parent().unwrap();
+    fs::create_dir_all(dir).context("While creating cache dir")?;
+    let mut tmp = tempfile::NamedTempFile::new_in(dir).context("While creating temp file")?;
+    tmp.write_all(&serde_json::to_vec(test_cases)?)
+        .context("While writing test cases")?;
+    tmp.persist(file).context("While moving cache file")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use amplitude_common::config_and_set_path;
+    use serde_json::json;
+
+    use super::*;
+    use crate::exercise::{runner_template, LanguageInfo};
+
+    fn exercise_config(seed: u64) -> ExerciseConfig {
+        let mut cfg: ExerciseConfig = toml::from_str(
+            "title = \"add\"\n[functions.add]\ninputs = [\"int\"]\noutput = \"int\"",
+        )
+        .unwrap();
+        cfg.functions.get_mut("add").unwrap().seed = seed;
+        cfg
+    }
+
+    #[test]
+    fn test_cache_key() {
+        let key = |image, config, program, seed| {
+            cache_key(
+                &Language::Python,
+                image,
+                config,
+                &[program],
+                &exercise_config(seed),
+            )
+        };
+
+        let base = key("python", "a", "b", 0);
+        assert_eq!(base.len(), 64);
+        assert_eq!(base, key("python", "a", "b", 0));
+        assert_ne!(base, key("python3", "a", "b", 0));
+        assert_ne!(base, key("python", "a", "bb", 0));
+        assert_ne!(base, key("python", "a", "b", 1));
+        // moving text between parts gives a different key
+        assert_ne!(base, key("python", "ab", "", 0));
+    }
+
+    #[test]
+    fn test_verify_cached() {
+        let mut cfg = config_and_set_path().unwrap();
+        let mut exercise_cfg = exercise_config(0);
+        let add = exercise_cfg.functions.get_mut("add").unwrap();
+        add.visible_cases = 1;
+        add.hidden_cases = 0;
+        // random, so it isn't cached by an earlier run of the test
+        let input = rand::random::<u32>();
+        let test = TestCase {
+            inputs: vec![json!(input)],
+            output: json!(input as u64 + 1),
+            hidden: false,
+        };
+        set_test_cases(
+            &mut exercise_cfg,
+            HashMap::from([("add".to_string(), vec![test])]),
+        )
+        .unwrap();
+
+        let lang = Language::Python;
+        let runner = runner_template(&lang, &exercise_cfg, "add").unwrap();
+        let lang_info = HashMap::from([(
+            lang,
+            LanguageInfo {
+                code: String::new(),
+                runner,
+            },
+        )]);
+        let exercise = Exercise::new(exercise_cfg, lang_info, None);
+
+        let solution = "def add(x):\n    return x + 1\n";
+        verify_cached(&exercise, &lang, solution, "add", &cfg).unwrap();
+        // nothing can be run anymore, so it has to come from the cache
+        cfg.docker.tmp_folder = "/nonexistent".to_string();
+        verify_cached(&exercise, &lang, solution, "add", &cfg).unwrap();
+        assert!(
+            verify_cached(&exercise, &lang, "def add(x):\n    return x\n", "add", &cfg).is_err()
+        );
+    }
+
+    #[test]
+    fn test_store() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("nested/key.json");
+        let test_cases = HashMap::from([(
+            "add".to_string(),
+            vec![TestCase {
+                inputs: vec![json!(1)],
+                output: json!(2),
+                hidden: false,
+            }],
+        )]);
+
+        store(&file, &test_cases).unwrap();
+        let read: HashMap<String, Vec<TestCase>> =
+            serde_json::from_slice(&fs::read(&file).unwrap()).unwrap();
+        assert_eq!(read["add"][0].output, json!(2));
+    }
+}
@@ -0,0 +1,470 @@
+//! Turns `VariableType`s and json values into source code for the test
+//! harnesses (`runner.hbs` / `generator.hbs`) of each language.
+
+use crate::{
+    exercise::{ExerciseConfig, FunctionConfig},
+    lang::Language,
+    var_type::VariableType,
+};
+
+use std::collections::{HashMap, HashSet};
+
+use anyhow::Context;
+use serde::Serialize;
+use serde_json::Value;
+
+mod c;
+mod cpp;
+mod java;
+mod javascript;
+mod python;
+mod rust;
+
+pub trait Codegen: Send + Sync {
+    /// The name of `ty` in this language
+    fn type_name(&self, ty: &VariableType) -> anyhow::Result<String>;
+
+    /// `value` written as a literal of type `ty` in this language
+    fn literal(&self, ty: &VariableType, value: &Value) -> anyhow::Result<String>;
+
+    /// Any definitions needed to use `types` (structs, tuples, serializers...).
+    /// `types` is ordered so that every type comes after the types it contains.
+    fn type_defs(&self, _types: &[VariableType]) -> anyhow::Result<String> {
+        Ok(String::new())
+    }
+
+    /// The name of the function that serializes a value of type `ty` to json
+    fn serializer(&self, _ty: &VariableType) -> String {
+        "to_json".to_string()
+    }
+
+    /// Starter code declaring `functions` with placeholder bodies, see
+    /// [`generate_stub`](crate::stub::generate_stub)
+    fn stub(&self, functions: &[(&String, &FunctionConfig)]) -> anyhow::Result<String>;
+}
+
+/// Get the code generator for a language
+pub fn codegen(lang: &Language) -> &'static dyn Codegen {
+    match lang {
+        Language::C => &c::C,
+        Language::Cpp => &cpp::Cpp,
+        Language::JavaScript => &javascript::JavaScript,
+        Language::Python => &python::Python,
+        Language::Rust => &rust::Rust,
+        Language::Java => &java::Java,
+    }
+}
+
+/// A variable as seen by a template
+#[derive(Debug, Serialize)]
+pub struct TemplateVar {
+    /// The native type name
+    pub ty: String,
+    /// The function that serializes it to json
+    pub json: String,
+}
+
+/// A test case as seen by a template
+#[derive(Debug, Serialize)]
+pub struct TemplateTest {
+    /// The inputs, as native literals
+    pub args: Vec<String>,
+    pub hidden: bool,
+}
+
+/// An output to check, as seen by a checker template
+#[derive(Debug, Serialize)]
+pub struct TemplateCheck {
+    /// The index of the test case
+    pub index: usize,
+    /// The inputs, as native literals
+    pub args: Vec<String>,
+    pub expected: String,
+    pub actual: String,
+}
+
+/// A function as seen by a template
+#[derive(Debug, Serialize)]
+pub struct TemplateFunction {
+    pub name: String,
+    pub inputs: Vec<TemplateVar>,
+    pub output: TemplateVar,
+    pub seed: u64,
+    pub hidden_cases: u32,
+    pub visible_cases: u32,
+    pub tests: Vec<TemplateTest>,
+    /// Only filled in for checkers, see [`TemplateData::add_checks`]
+    pub checks: Vec<TemplateCheck>,
+}
+
+/// Everything the templates need to know about the functions of an exercise
+#[derive(Debug, Serialize)]
+pub struct TemplateData {
+    pub functions: Vec<TemplateFunction>,
+    pub type_defs: String,
+}
+
+impl TemplateData {
+    pub fn new(lang: &Language, cfg: &ExerciseConfig) -> anyhow::Result<Self> {
+        let gen = codegen(lang);
+        let var = |ty: &VariableType| -> anyhow::Result<_> {
+            Ok(TemplateVar {
+                ty: gen.type_name(ty)?,
+                json: gen.serializer(ty),
+            })
+        };
+
+        // sorted so the generated code is the same every time
+        let mut names = cfg.functions.keys().collect::<Vec<_>>();
+        names.sort();
+
+        let mut functions = Vec::new();
+        for name in names {
+            let func = &cfg.functions[name];
+            check_ident(name).context("Invalid function name")?;
+
+            let tests = func
+                .tests
+                .iter()
+                .map(|test| -> anyhow::Result<_> {
+                    anyhow::ensure!(
+                        test.inputs.len() == func.inputs.len(),
+                        "Expected {} inputs for `{name}`, found {}",
+                        func.inputs.len(),
+                        test.inputs.len()
+                    );
+                    let args = func
+                        .inputs
+                        .iter()
+                        .zip(&test.inputs)
+                        .map(|(ty, value)| gen.literal(ty, value))
+                        .collect::<anyhow::Result<_>>()?;
+                    Ok(TemplateTest {
+                        args,
+                        hidden: test.hidden,
+                    })
+                })
+                .collect::<anyhow::Result<_>>()
+                .with_context(|| format!("While writing test cases for `{name}`"))?;
+
+            functions.push(TemplateFunction {
+                name: name.clone(),
+                inputs: func.inputs.iter().map(var).collect::<anyhow::Result<_>>()?,
+                output: var(&func.output)?,
+                seed: func.seed,
+                hidden_cases: func.hidden_cases,
+                visible_cases: func.visible_cases,
+                tests,
+                checks: Vec::new(),
+            });
+        }
+
+        let types = collect_types(
+            cfg.functions
+                .values()
+                .flat_map(|f| f.inputs.iter().chain([&f.output]))
+                .chain(cfg.types.values()),
+        );
+        // a named type could be called the same as a generated one, like
+        // `ArrayOfInt`
+        let mut mangled = HashSet::new();
+        for ty in &types {
+            anyhow::ensure!(
+                mangled.insert(mangle(ty)),
+                "The name of type `{ty}` is already used by another type"
+            );
+        }
+
+        Ok(Self {
+            functions,
+            type_defs: gen.type_defs(&types)?,
+        })
+    }
+}
+
+impl TemplateData {
+    /// Add the outputs to check for each function, as `(test index, output)`.
+    /// Every output has to be of the function's output type.
+    pub fn add_checks(
+        &mut self,
+        lang: &Language,
+        cfg: &ExerciseConfig,
+        outputs: &HashMap<String, Vec<(usize, Value)>>,
+    ) -> anyhow::Result<()> {
+        let gen = codegen(lang);
+        for func in &mut self.functions {
+            let Some(outputs) = outputs.get(&func.name) else {
+                continue;
+            };
+            let cfg = &cfg.functions[&func.name];
+            for (index, actual) in outputs {
+                let test = cfg.tests.get(*index).context("Test case doesn't exist")?;
+                let args = cfg
+                    .inputs
+                    .iter()
+                    .zip(&test.inputs)
+                    .map(|(ty, value)| gen.literal(ty, value))
+                    .collect::<anyhow::Result<_>>()?;
+                func.checks.push(TemplateCheck {
+                    index: *index,
+                    args,
+                    expected: gen.literal(&cfg.output, &test.output)?,
+                    actual: gen.literal(&cfg.output, actual)?,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Every type used by `types` (including the types they contain), without
+/// duplicates, with every type after the types it contains.
+pub fn collect_types<'a>(types: impl IntoIterator<Item = &'a VariableType>) -> Vec<VariableType> {
+    fn visit(ty: &VariableType, out: &mut Vec<VariableType>) {
+        match ty {
+            VariableType::Array(inner) | VariableType::Optional(inner) => visit(inner, out),
+            VariableType::Map(key, value) => {
+                visit(key, out);
+                visit(value, out);
+            }
+            VariableType::Tuple(fields) => fields.iter().for_each(|ty| visit(ty, out)),
+            VariableType::Struct(_) | VariableType::Named(_) => struct_fields(ty)
+                .into_iter()
+                .for_each(|(_, ty)| visit(ty, out)),
+            _ => {}
+        }
+        if !out.contains(ty) {
+            out.push(ty.clone());
+        }
+    }
+
+    let mut types = types.into_iter().collect::<Vec<_>>();
+    // sorted so the generated code is the same every time
+    types.sort_by_key(|ty| mangle(ty));
+
+    let mut out = Vec::new();
+    for ty in types {
+        visit(ty, &mut out);
+    }
+    out
+}
+
+/// A name for `ty` that is a valid identifier in every language, and unique to
+/// that type.
+pub fn mangle(ty: &VariableType) -> String {
+    match ty {
+        VariableType::Int => "Int".to_string(),
+        VariableType::Float => "Float".to_string(),
+        VariableType::String => "String".to_string(),
+        VariableType::Char => "Char".to_string(),
+        VariableType::Boolean => "Bool".to_string(),
+        VariableType::Array(inner) => format!("ArrayOf{}", mangle(inner)),
+        VariableType::Optional(inner) => format!("OptionOf{}", mangle(inner)),
+        // keys are always primitives, so this can't be ambiguous
+        VariableType::Map(key, value) => format!("MapOf{}To{}", mangle(key), mangle(value)),
+        VariableType::Tuple(fields) => {
+            let mangled = fields.iter().map(mangle).collect::<String>();
+            format!("Tuple{}{mangled}", fields.len())
+        }
+        VariableType::Struct(fields) => {
+            let mangled = struct_fields(ty)
+                .iter()
+                .map(|(name, ty)| format!("{}{name}{}", name.len(), mangle(ty)))
+                .collect::<String>();
+            format!("Struct{}_{mangled}", fields.len())
+        }
+        // named types have to start with an uppercase letter, and can't be
+        // the same as any other mangled name, see `TemplateData::new`
+        VariableType::Named(named) => named.name.clone(),
+    }
+}
+
+/// The fields of a struct or named type, in the order they are declared in
+/// generated code. Named types keep the order of their declaration.
+pub fn struct_fields(ty: &VariableType) -> Vec<(&String, &VariableType)> {
+    match ty {
+        VariableType::Struct(fields) => {
+            let mut fields = fields.iter().collect::<Vec<_>>();
+            fields.sort_by_key(|(name, _)| *name);
+            fields
+        }
+        VariableType::Named(named) => named.fields.iter().map(|x| (&x.name, &x.ty)).collect(),
+        _ => vec![],
+    }
+}
+
+/// The names of the parameters of a function in generated stubs
+pub(crate) fn param_names(func: &FunctionConfig) -> Vec<String> {
+    (1..=func.inputs.len()).map(|i| format!("arg{i}")).collect()
+}
+
+/// Make sure a name can be used as an identifier in generated code
+pub fn check_ident(name: &str) -> anyhow::Result<()> {
+    let mut chars = name.chars();
+    anyhow::ensure!(
+        chars
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && chars.all(|c| c.is_ascii_alphanumeric() || c == '_'),
+        "`{name}` is not a valid identifier"
+    );
+    Ok(())
+}
+
+fn type_error(ty: &VariableType, value: &Value) -> anyhow::Error {
+    anyhow::anyhow!("Expected a value of type `{ty}`, found `{value}`")
+}
+
+pub(crate) fn as_int(ty: &VariableType, value: &Value) -> anyhow::Result<i64> {
+    value.as_i64().ok_or_else(|| type_error(ty, value))
+}
+
+pub(crate) fn as_float(ty: &VariableType, value: &Value) -> anyhow::Result<f64> {
+    value.as_f64().ok_or_else(|| type_error(ty, value))
+}
+
+pub(crate) fn as_str<'a>(ty: &VariableType, value: &'a Value) -> anyhow::Result<&'a str> {
+    value.as_str().ok_or_else(|| type_error(ty, value))
+}
+
+pub(crate) fn as_char(ty: &VariableType, value: &Value) -> anyhow::Result<char> {
+    let mut chars = as_str(ty, value)?.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Ok(c),
+        _ => Err(type_error(ty, value)),
+    }
+}
+
+pub(crate) fn as_bool(ty: &VariableType, value: &Value) -> anyhow::Result<bool> {
+    value.as_bool().ok_or_else(|| type_error(ty, value))
+}
+
+pub(crate) fn as_array<'a>(ty: &VariableType, value: &'a Value) -> anyhow::Result<&'a Vec<Value>> {
+    let array = value.as_array().ok_or_else(|| type_error(ty, value))?;
+    if let VariableType::Tuple(fields) = ty {
+        anyhow::ensure!(array.len() == fields.len(), type_error(ty, value));
+    }
+    Ok(array)
+}
+
+/// The `(key, value)` pairs of a map
+pub(crate) fn map_entries<'a>(
+    ty: &VariableType,
+    value: &'a Value,
+) -> anyhow::Result<Vec<(&'a Value, &'a Value)>> {
+    let pairs = value.as_array().ok_or_else(|| type_error(ty, value))?;
+    pairs
+        .iter()
+        .map(|pair| match pair.as_array().map(|x| &x[..]) {
+            Some([key, value]) => Ok((key, value)),
+            _ => Err(type_error(ty, pair)),
+        })
+        .collect()
+}
+
+/// The values of a struct, in the same order as `struct_fields`
+pub(crate) fn struct_values<'a>(
+    ty: &'a VariableType,
+    value: &'a Value,
+) -> anyhow::Result<Vec<(&'a String, &'a VariableType, &'a Value)>> {
+    let object = value.as_object().ok_or_else(|| type_error(ty, value))?;
+    let fields = struct_fields(ty);
+    anyhow::ensure!(object.len() == fields.len(), type_error(ty, value));
+    fields
+        .into_iter()
+        .map(|(name, ty)| {
+            let value = object
+                .get(name)
+                .with_context(|| format!("Missing field `{name}` in `{value}`"))?;
+            Ok((name, ty, value))
+        })
+        .collect()
+}
+
+/// A float literal that is valid in C-like languages (and rust), always
+/// containing a `.` or an exponent
+pub(crate) fn float_literal(f: f64) -> String {
+    // `Debug` always includes a `.` or exponent, unlike `Display`
+    format!("{f:?}")
+}
+
+/// A string literal that works in C and C++, escaping anything that isn't
+/// printable ascii byte by byte.
+pub(crate) fn c_string_literal(s: &str) -> String {
+    let mut out = String::from("\"");
+    for b in s.bytes() {
+        match b {
+            b'"' => out.push_str("\\\""),
+            b'\\' => out.push_str("\\\\"),
+            b'\n' => out.push_str("\\n"),
+            b'\r' => out.push_str("\\r"),
+            b'\t' => out.push_str("\\t"),
+            0x20..=0x7e => out.push(b as char),
+            // octal escapes are never more than 3 digits, unlike hex ones
+            _ => out.push_str(&format!("\\{b:03o}")),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// A char literal that works in C and C++. Their `char` is a single byte, so
+/// only ascii characters are allowed.
+pub(crate) fn c_char_literal(c: char) -> anyhow::Result<String> {
+    anyhow::ensure!(
+        c.is_ascii(),
+        "`{c}` doesn't fit in a `char`, only ascii is supported"
+    );
+    Ok(match c {
+        '\'' => "'\\''".to_string(),
+        '"' => "'\"'".to_string(),
+        // the same escapes as in strings
+        _ => {
+            let s = c_string_literal(&c.to_string());
+            format!("'{}'", &s[1..s.len() - 1])
+        }
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn ty(s: &str) -> VariableType {
+        VariableType::try_from(s).unwrap()
+    }
+
+    #[test]
+    fn test_mangle() {
+        assert_eq!(mangle(&ty("int[][]")), "ArrayOfArrayOfInt");
+        assert_eq!(mangle(&ty("(int, string[])")), "Tuple2IntArrayOfString");
+        assert_eq!(mangle(&ty("{y: float, x: int}")), "Struct2_1xInt1yFloat");
+        assert_eq!(
+            mangle(&ty("map<char, int?[]>")),
+            "MapOfCharToArrayOfOptionOfInt"
+        );
+    }
+
+    #[test]
+    fn test_collect_types() {
+        let types = [ty("(int, string)[]"), ty("int")];
+        assert_eq!(
+            collect_types(&types),
+            [
+                ty("int"),
+                ty("string"),
+                ty("(int, string)"),
+                ty("(int, string)[]")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_c_string_literal() {
+        assert_eq!(c_string_literal("a\"b\\\n"), r#""a\"b\\\n""#);
+        assert_eq!(c_string_literal("é1"), r#""\303\2511""#);
+        assert_eq!(c_char_literal('\'').unwrap(), r"'\''");
+        assert_eq!(c_char_literal('\n').unwrap(), r"'\n'");
+        assert!(c_char_literal('é').is_err());
+    }
+}
@@ -0,0 +1,235 @@
+use super::*;
+
+pub struct C;
+
+/// A float literal that also works for `inf` and `nan` (needs `math.h`)
+pub(super) fn c_float_literal(f: f64) -> String {
+    match f {
+        _ if f.is_finite() => float_literal(f),
+        _ if f.is_nan() => "NAN".to_string(),
+        _ if f > 0.0 => "INFINITY".to_string(),
+        _ => "-INFINITY".to_string(),
+    }
+}
+
+/// An integer literal that also works for `i64::MIN`
+pub(super) fn c_int_literal(i: i64) -> String {
+    match i {
+        i64::MIN => "(-9223372036854775807LL - 1)".to_string(),
+        _ => format!("{i}LL"),
+    }
+}
+
+impl Codegen for C {
+    fn type_name(&self, ty: &VariableType) -> anyhow::Result<String> {
+        Ok(match ty {
+            VariableType::Int => "long long".to_string(),
+            VariableType::Float => "double".to_string(),
+            VariableType::String => "char*".to_string(),
+            VariableType::Char => "char".to_string(),
+            VariableType::Boolean => "bool".to_string(),
+            VariableType::Array(_)
+            | VariableType::Tuple(_)
+            | VariableType::Struct(_)
+            | VariableType::Named(_)
+            | VariableType::Optional(_)
+            | VariableType::Map(..) => mangle(ty),
+        })
+    }
+
+    fn literal(&self, ty: &VariableType, value: &Value) -> anyhow::Result<String> {
+        Ok(match ty {
+            VariableType::Int => c_int_literal(as_int(ty, value)?),
+            VariableType::Float => c_float_literal(as_float(ty, value)?),
+            VariableType::String => c_string_literal(as_str(ty, value)?),
+            VariableType::Char => c_char_literal(as_char(ty, value)?)?,
+            VariableType::Boolean => as_bool(ty, value)?.to_string(),
+            VariableType::Array(inner) => {
+                let items = as_array(ty, value)?
+                    .iter()
+                    .map(|v| self.literal(inner, v))
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+                match items.is_empty() {
+                    // empty compound literals aren't allowed
+                    true => format!("({}){{NULL, 0}}", mangle(ty)),
+                    false => format!(
+                        "({}){{({}[]){{{}}}, {}}}",
+                        mangle(ty),
+                        self.type_name(inner)?,
+                        items.join(", "),
+                        items.len()
+                    ),
+                }
+            }
+            VariableType::Optional(inner) => match value.is_null() {
+                true => format!("({}){{false}}", mangle(ty)),
+                false => format!("({}){{true, {}}}", mangle(ty), self.literal(inner, value)?),
+            },
+            VariableType::Map(key_ty, value_ty) => {
+                let entries = map_entries(ty, value)?;
+                let keys = entries
+                    .iter()
+                    .map(|(k, _)| self.literal(key_ty, k))
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+                let values = entries
+                    .iter()
+                    .map(|(_, v)| self.literal(value_ty, v))
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+                match entries.is_empty() {
+                    true => format!("({}){{NULL, NULL, 0}}", mangle(ty)),
+                    false => format!(
+                        "({}){{({}[]){{{}}}, ({}[]){{{}}}, {}}}",
+                        mangle(ty),
+                        self.type_name(key_ty)?,
+                        keys.join(", "),
+                        self.type_name(value_ty)?,
+                        values.join(", "),
+                        entries.len()
+                    ),
+                }
+            }
+            VariableType::Tuple(fields) => {
+                let items = fields
+                    .iter()
+                    .zip(as_array(ty, value)?)
+                    .map(|(ty, v)| self.literal(ty, v))
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+                format!("({}){{{}}}", mangle(ty), items.join(", "))
+            }
+            VariableType::Struct(_) | VariableType::Named(_) => {
+                let items = struct_values(ty, value)?
+                    .into_iter()
+                    .map(|(name, ty, v)| Ok(format!(".{name} = {}", self.literal(ty, v)?)))
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+                format!("({}){{{}}}", mangle(ty), items.join(", "))
+            }
+        })
+    }
+
+    fn type_defs(&self, types: &[VariableType]) -> anyhow::Result<String> {
+        let mut out = String::new();
+
+        for ty in types {
+            let name = mangle(ty);
+            let fields = match ty {
+                VariableType::Array(inner) => {
+                    vec![
+                        format!("{}* data;", self.type_name(inner)?),
+                        "size_t len;".to_string(),
+                    ]
+                }
+                VariableType::Optional(inner) => vec![
+                    "bool has_value;".to_string(),
+                    format!("{} value;", self.type_name(inner)?),
+                ],
+                VariableType::Map(key, value) => vec![
+                    format!("{}* keys;", self.type_name(key)?),
+                    format!("{}* values;", self.type_name(value)?),
+                    "size_t len;".to_string(),
+                ],
+                VariableType::Tuple(fields) => fields
+                    .iter()
+                    .enumerate()
+                    .map(|(i, ty)| Ok(format!("{} _{i};", self.type_name(ty)?)))
+                    .collect::<anyhow::Result<_>>()?,
+                VariableType::Struct(_) | VariableType::Named(_) => struct_fields(ty)
+                    .into_iter()
+                    .map(|(name, ty)| {
+                        check_ident(name)?;
+                        Ok(format!("{} {name};", self.type_name(ty)?))
+                    })
+                    .collect::<anyhow::Result<_>>()?,
+                _ => continue,
+            };
+
+            out += "typedef struct {\n";
+            for field in fields {
+                out += &format!("    {field}\n");
+            }
+            out += &format!("}} {name};\n\n");
+        }
+
+        // serializers, which print to stdout
+        for ty in types {
+            let name = mangle(ty);
+            let body = match ty {
+                VariableType::Array(inner) => format!(
+                    "    putchar('[');\n    \
+                    for (size_t i = 0; i < v.len; i++) {{\n        \
+                        if (i) putchar(',');\n        \
+                        {}(v.data[i]);\n    \
+                    }}\n    \
+                    putchar(']');\n",
+                    self.serializer(inner)
+                ),
+                VariableType::Optional(inner) => format!(
+                    "    if (!v.has_value) {{\n        \
+                        fputs(\"null\", stdout);\n        \
+                        return;\n    \
+                    }}\n    \
+                    {}(v.value);\n",
+                    self.serializer(inner)
+                ),
+                VariableType::Map(key, value) => format!(
+                    "    putchar('[');\n    \
+                    for (size_t i = 0; i < v.len; i++) {{\n        \
+                        if (i) putchar(',');\n        \
+                        putchar('[');\n        \
+                        {}(v.keys[i]);\n        \
+                        putchar(',');\n        \
+                        {}(v.values[i]);\n        \
+                        putchar(']');\n    \
+                    }}\n    \
+                    putchar(']');\n",
+                    self.serializer(key),
+                    self.serializer(value)
+                ),
+                VariableType::Tuple(fields) => {
+                    let mut body = "    putchar('[');\n".to_string();
+                    for (i, ty) in fields.iter().enumerate() {
+                        if i != 0 {
+                            body += "    putchar(',');\n";
+                        }
+                        body += &format!("    {}(v._{i});\n", self.serializer(ty));
+                    }
+                    body + "    putchar(']');\n"
+                }
+                VariableType::Struct(_) | VariableType::Named(_) => {
+                    let mut body = "    putchar('{');\n".to_string();
+                    for (i, (field, ty)) in struct_fields(ty).into_iter().enumerate() {
+                        let comma = if i == 0 { "" } else { "," };
+                        body += &format!("    fputs(\"{comma}\\\"{field}\\\":\", stdout);\n");
+                        body += &format!("    {}(v.{field});\n", self.serializer(ty));
+                    }
+                    body + "    putchar('}');\n"
+                }
+                _ => continue,
+            };
+            out += &format!("void json_{name}({name} v) {{\n{body}}}\n\n");
+        }
+
+        Ok(out)
+    }
+
+    fn serializer(&self, ty: &VariableType) -> String {
+        format!("json_{}", mangle(ty))
+    }
+
+    fn stub(&self, functions: &[(&String, &FunctionConfig)]) -> anyhow::Result<String> {
+        let mut out = Vec::new();
+        for (name, func) in functions {
+            let params = param_names(func)
+                .into_iter()
+                .zip(&func.inputs)
+                .map(|(param, ty)| Ok(format!("{} {param}", self.type_name(ty)?)))
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            let output = self.type_name(&func.output)?;
+            // a zeroed compound literal works for every type
+            out.push(format!(
+                "{output} {name}({}) {{\n    return ({output}){{0}};\n}}\n",
+                params.join(", ")
+            ));
+        }
+        Ok(out.join("\n"))
+    }
+}
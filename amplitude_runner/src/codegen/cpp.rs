@@ -0,0 +1,131 @@
+use super::{
+    c::{c_float_literal, c_int_literal},
+    *,
+};
+
+pub struct Cpp;
+
+impl Codegen for Cpp {
+    fn type_name(&self, ty: &VariableType) -> anyhow::Result<String> {
+        Ok(match ty {
+            VariableType::Int => "long long".to_string(),
+            VariableType::Float => "double".to_string(),
+            VariableType::String => "std::string".to_string(),
+            VariableType::Char => "char".to_string(),
+            VariableType::Boolean => "bool".to_string(),
+            VariableType::Array(inner) => format!("std::vector<{}>", self.type_name(inner)?),
+            VariableType::Optional(inner) => format!("std::optional<{}>", self.type_name(inner)?),
+            VariableType::Map(key, value) => format!(
+                "std::map<{}, {}>",
+                self.type_name(key)?,
+                self.type_name(value)?
+            ),
+            VariableType::Tuple(fields) => {
+                let fields = fields
+                    .iter()
+                    .map(|ty| self.type_name(ty))
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+                format!("std::tuple<{}>", fields.join(", "))
+            }
+            VariableType::Struct(_) | VariableType::Named(_) => mangle(ty),
+        })
+    }
+
+    fn literal(&self, ty: &VariableType, value: &Value) -> anyhow::Result<String> {
+        Ok(match ty {
+            VariableType::Int => c_int_literal(as_int(ty, value)?),
+            VariableType::Float => c_float_literal(as_float(ty, value)?),
+            VariableType::String => {
+                format!("std::string({})", c_string_literal(as_str(ty, value)?))
+            }
+            VariableType::Char => c_char_literal(as_char(ty, value)?)?,
+            VariableType::Boolean => as_bool(ty, value)?.to_string(),
+            VariableType::Optional(inner) => match value.is_null() {
+                true => format!("{}()", self.type_name(ty)?),
+                false => format!("{}({})", self.type_name(ty)?, self.literal(inner, value)?),
+            },
+            VariableType::Map(key_ty, value_ty) => {
+                let items = map_entries(ty, value)?
+                    .into_iter()
+                    .map(|(k, v)| {
+                        Ok(format!(
+                            "{{{}, {}}}",
+                            self.literal(key_ty, k)?,
+                            self.literal(value_ty, v)?
+                        ))
+                    })
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+                format!("{}{{{}}}", self.type_name(ty)?, items.join(", "))
+            }
+            VariableType::Array(inner) => {
+                let items = as_array(ty, value)?
+                    .iter()
+                    .map(|v| self.literal(inner, v))
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+                format!("{}{{{}}}", self.type_name(ty)?, items.join(", "))
+            }
+            VariableType::Tuple(fields) => {
+                let items = fields
+                    .iter()
+                    .zip(as_array(ty, value)?)
+                    .map(|(ty, v)| self.literal(ty, v))
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+                format!("{}{{{}}}", self.type_name(ty)?, items.join(", "))
+            }
+            VariableType::Struct(_) | VariableType::Named(_) => {
+                let items = struct_values(ty, value)?
+                    .into_iter()
+                    .map(|(_, ty, v)| self.literal(ty, v))
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+                format!("{}{{{}}}", mangle(ty), items.join(", "))
+            }
+        })
+    }
+
+    fn type_defs(&self, types: &[VariableType]) -> anyhow::Result<String> {
+        let mut out = String::new();
+
+        // everything else is handled by the templates in the prelude
+        for ty in types
+            .iter()
+            .filter(|ty| matches!(ty, VariableType::Struct(_) | VariableType::Named(_)))
+        {
+            let name = mangle(ty);
+            let fields = struct_fields(ty);
+
+            out += &format!("struct {name} {{\n");
+            for (field, ty) in &fields {
+                check_ident(field)?;
+                out += &format!("    {} {field};\n", self.type_name(ty)?);
+            }
+            out += "};\n\n";
+
+            out += &format!("std::string to_json(const {name}& v) {{\n");
+            out += "    std::string out = \"{\";\n";
+            for (i, (field, _)) in fields.iter().enumerate() {
+                let comma = if i == 0 { "" } else { "," };
+                out += &format!("    out += \"{comma}\\\"{field}\\\":\" + to_json(v.{field});\n");
+            }
+            out += "    return out + \"}\";\n}\n\n";
+        }
+
+        Ok(out)
+    }
+
+    fn stub(&self, functions: &[(&String, &FunctionConfig)]) -> anyhow::Result<String> {
+        let mut out = Vec::new();
+        for (name, func) in functions {
+            let params = param_names(func)
+                .into_iter()
+                .zip(&func.inputs)
+                .map(|(param, ty)| Ok(format!("{} {param}", self.type_name(ty)?)))
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            out.push(format!(
+                "{} {name}({}) {{\n    return {{}};\n}}\n",
+                self.type_name(&func.output)?,
+                params.join(", ")
+            ));
+        }
+        Ok(out.join("\n"))
+    }
+}
@@ -0,0 +1,189 @@
+use super::*;
+
+pub struct Java;
+
+impl Codegen for Java {
+    fn type_name(&self, ty: &VariableType) -> anyhow::Result<String> {
+        Ok(match ty {
+            VariableType::Int => "long".to_string(),
+            VariableType::Float => "double".to_string(),
+            VariableType::String => "String".to_string(),
+            VariableType::Char => "char".to_string(),
+            VariableType::Boolean => "boolean".to_string(),
+            VariableType::Array(inner) => format!("{}[]", self.type_name(inner)?),
+            VariableType::Tuple(_) | VariableType::Struct(_) | VariableType::Named(_) => mangle(ty),
+            // primitives can't be `null` or used in generics
+            VariableType::Optional(inner) => self.boxed_type_name(inner)?,
+            // the solution is in its own file, which might not import it
+            VariableType::Map(key, value) => format!(
+                "java.util.HashMap<{}, {}>",
+                self.boxed_type_name(key)?,
+                self.boxed_type_name(value)?
+            ),
+        })
+    }
+
+    fn literal(&self, ty: &VariableType, value: &Value) -> anyhow::Result<String> {
+        Ok(match ty {
+            VariableType::Int => format!("{}L", as_int(ty, value)?),
+            VariableType::Float => match as_float(ty, value)? {
+                f if f.is_finite() => float_literal(f),
+                f if f.is_nan() => "Double.NaN".to_string(),
+                f if f > 0.0 => "Double.POSITIVE_INFINITY".to_string(),
+                _ => "Double.NEGATIVE_INFINITY".to_string(),
+            },
+            VariableType::String => java_string_literal(as_str(ty, value)?)?,
+            VariableType::Char => java_char_literal(as_char(ty, value)?)?,
+            VariableType::Boolean => as_bool(ty, value)?.to_string(),
+            // literals of primitives are boxed automatically
+            VariableType::Optional(inner) => match value.is_null() {
+                true => "null".to_string(),
+                false => self.literal(inner, value)?,
+            },
+            VariableType::Map(key_ty, value_ty) => {
+                let puts = map_entries(ty, value)?
+                    .into_iter()
+                    .map(|(k, v)| {
+                        Ok(format!(
+                            "put({}, {}); ",
+                            self.literal(key_ty, k)?,
+                            self.literal(value_ty, v)?
+                        ))
+                    })
+                    .collect::<anyhow::Result<String>>()?;
+                match puts.is_empty() {
+                    true => format!("new {}()", self.type_name(ty)?),
+                    false => format!("new {}() {{{{ {puts}}}}}", self.type_name(ty)?),
+                }
+            }
+            VariableType::Array(inner) => {
+                let items = as_array(ty, value)?
+                    .iter()
+                    .map(|v| self.literal(inner, v))
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+                format!("new {}{{{}}}", self.type_name(ty)?, items.join(", "))
+            }
+            VariableType::Tuple(fields) => {
+                let items = fields
+                    .iter()
+                    .zip(as_array(ty, value)?)
+                    .map(|(ty, v)| self.literal(ty, v))
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+                format!("new {}({})", mangle(ty), items.join(", "))
+            }
+            VariableType::Struct(_) | VariableType::Named(_) => {
+                let items = struct_values(ty, value)?
+                    .into_iter()
+                    .map(|(_, ty, v)| self.literal(ty, v))
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+                format!("new {}({})", mangle(ty), items.join(", "))
+            }
+        })
+    }
+
+    fn type_defs(&self, types: &[VariableType]) -> anyhow::Result<String> {
+        let mut out = String::new();
+
+        // records are serialized with reflection, tuples as arrays and structs
+        // as objects
+        for ty in types {
+            let (fields, implements) = match ty {
+                VariableType::Tuple(fields) => (
+                    fields
+                        .iter()
+                        .enumerate()
+                        .map(|(i, ty)| Ok(format!("{} _{i}", self.type_name(ty)?)))
+                        .collect::<anyhow::Result<Vec<_>>>()?,
+                    " implements Tuple",
+                ),
+                VariableType::Struct(_) | VariableType::Named(_) => (
+                    struct_fields(ty)
+                        .into_iter()
+                        .map(|(name, ty)| {
+                            check_ident(name)?;
+                            Ok(format!("{} {name}", self.type_name(ty)?))
+                        })
+                        .collect::<anyhow::Result<Vec<_>>>()?,
+                    "",
+                ),
+                _ => continue,
+            };
+            out += &format!(
+                "record {}({}){implements} {{}}\n\n",
+                mangle(ty),
+                fields.join(", ")
+            );
+        }
+
+        Ok(out)
+    }
+
+    fn stub(&self, functions: &[(&String, &FunctionConfig)]) -> anyhow::Result<String> {
+        let mut methods = Vec::new();
+        for (name, func) in functions {
+            let params = param_names(func)
+                .into_iter()
+                .zip(&func.inputs)
+                .map(|(param, ty)| Ok(format!("{} {param}", self.type_name(ty)?)))
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            methods.push(format!(
+                "    public static {} {name}({}) {{\n        \
+                 throw new UnsupportedOperationException(\"Not implemented\");\n    }}\n",
+                self.type_name(&func.output)?,
+                params.join(", ")
+            ));
+        }
+        // the runner calls the methods on `Solution`, see `Language::code_path`
+        Ok(format!(
+            "public class Solution {{\n{}}}\n",
+            methods.join("\n")
+        ))
+    }
+}
+
+impl Java {
+    /// The name of `ty`, using the wrapper classes of primitives
+    fn boxed_type_name(&self, ty: &VariableType) -> anyhow::Result<String> {
+        Ok(match ty {
+            VariableType::Int => "Long".to_string(),
+            VariableType::Float => "Double".to_string(),
+            VariableType::Char => "Character".to_string(),
+            VariableType::Boolean => "Boolean".to_string(),
+            _ => self.type_name(ty)?,
+        })
+    }
+}
+
+/// A char literal, with everything that isn't printable ascii escaped
+fn java_char_literal(c: char) -> anyhow::Result<String> {
+    anyhow::ensure!(
+        c.len_utf16() == 1,
+        "`{c}` doesn't fit in a `char`, it's outside of the basic multilingual plane"
+    );
+    Ok(match c {
+        '\'' => "'\\''".to_string(),
+        '\\' => "'\\\\'".to_string(),
+        '\n' => "'\\n'".to_string(),
+        '\r' => "'\\r'".to_string(),
+        '\t' => "'\\t'".to_string(),
+        ' '..='~' => format!("'{c}'"),
+        _ => format!("'\\u{:04x}'", c as u32),
+    })
+}
+
+/// A json string with everything that isn't ascii escaped, so it doesn't
+/// depend on the encoding `javac` expects
+fn java_string_literal(s: &str) -> anyhow::Result<String> {
+    let mut out = String::new();
+    for c in serde_json::to_string(s)?.chars() {
+        match c.is_ascii() {
+            true => out.push(c),
+            false => {
+                for unit in c.encode_utf16(&mut [0; 2]) {
+                    out += &format!("\\u{unit:04x}");
+                }
+            }
+        }
+    }
+    Ok(out)
+}
@@ -0,0 +1,184 @@
+use super::*;
+
+pub struct JavaScript;
+
+impl Codegen for JavaScript {
+    fn type_name(&self, ty: &VariableType) -> anyhow::Result<String> {
+        // jsdoc types
+        Ok(match ty {
+            VariableType::Int | VariableType::Float => "number".to_string(),
+            VariableType::String | VariableType::Char => "string".to_string(),
+            VariableType::Boolean => "boolean".to_string(),
+            VariableType::Array(inner) => format!("{}[]", self.type_name(inner)?),
+            VariableType::Optional(inner) => format!("({} | null)", self.type_name(inner)?),
+            VariableType::Map(key, value) => {
+                format!("Map<{}, {}>", self.type_name(key)?, self.type_name(value)?)
+            }
+            VariableType::Tuple(fields) => {
+                let fields = fields
+                    .iter()
+                    .map(|ty| self.type_name(ty))
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+                format!("[{}]", fields.join(", "))
+            }
+            VariableType::Struct(_) => {
+                let fields = struct_fields(ty)
+                    .into_iter()
+                    .map(|(name, ty)| Ok(format!("{name}: {}", self.type_name(ty)?)))
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+                format!("{{{}}}", fields.join(", "))
+            }
+            VariableType::Named(named) => named.name.clone(),
+        })
+    }
+
+    fn literal(&self, ty: &VariableType, value: &Value) -> anyhow::Result<String> {
+        Ok(match ty {
+            VariableType::Int => as_int(ty, value)?.to_string(),
+            VariableType::Float => {
+                let f = as_float(ty, value)?;
+                match f {
+                    _ if f.is_finite() => float_literal(f),
+                    _ if f.is_nan() => "NaN".to_string(),
+                    _ if f > 0.0 => "Infinity".to_string(),
+                    _ => "-Infinity".to_string(),
+                }
+            }
+            VariableType::String => serde_json::to_string(as_str(ty, value)?)?,
+            VariableType::Char => serde_json::to_string(&as_char(ty, value)?)?,
+            VariableType::Boolean => as_bool(ty, value)?.to_string(),
+            VariableType::Array(inner) => {
+                let items = as_array(ty, value)?
+                    .iter()
+                    .map(|v| self.literal(inner, v))
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+                format!("[{}]", items.join(", "))
+            }
+            VariableType::Optional(inner) => match value.is_null() {
+                true => "null".to_string(),
+                false => self.literal(inner, value)?,
+            },
+            VariableType::Map(key_ty, value_ty) => {
+                let items = map_entries(ty, value)?
+                    .into_iter()
+                    .map(|(k, v)| {
+                        Ok(format!(
+                            "[{}, {}]",
+                            self.literal(key_ty, k)?,
+                            self.literal(value_ty, v)?
+                        ))
+                    })
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+                format!("new Map([{}])", items.join(", "))
+            }
+            VariableType::Tuple(fields) => {
+                let items = fields
+                    .iter()
+                    .zip(as_array(ty, value)?)
+                    .map(|(ty, v)| self.literal(ty, v))
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+                format!("[{}]", items.join(", "))
+            }
+            VariableType::Struct(_) | VariableType::Named(_) => {
+                let items = struct_values(ty, value)?
+                    .into_iter()
+                    .map(|(name, ty, v)| {
+                        Ok(format!(
+                            "{}: {}",
+                            serde_json::to_string(name)?,
+                            self.literal(ty, v)?
+                        ))
+                    })
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+                format!("{{{}}}", items.join(", "))
+            }
+        })
+    }
+
+    fn type_defs(&self, types: &[VariableType]) -> anyhow::Result<String> {
+        let mut out = String::new();
+
+        // named types are plain objects, declared so they can be used in jsdoc
+        for ty in types {
+            let VariableType::Named(named) = ty else {
+                continue;
+            };
+            out += &format!("/**\n * @typedef {{Object}} {}\n", named.name);
+            for (name, ty) in struct_fields(ty) {
+                check_ident(name)?;
+                out += &format!(" * @property {{{}}} {name}\n", self.type_name(ty)?);
+            }
+            out += " */\n\n";
+        }
+
+        // serializers, so that floats are always written as floats
+        for ty in types {
+            let items = match ty {
+                VariableType::Array(inner) => format!("v.map({})", self.serializer(inner)),
+                VariableType::Map(key, value) => format!(
+                    "[...v].map(([k, x]) => \"[\" + {}(k) + \",\" + {}(x) + \"]\")",
+                    self.serializer(key),
+                    self.serializer(value)
+                ),
+                VariableType::Optional(inner) => {
+                    out += &format!(
+                        "function json_{}(v) {{\n    return v === null || v === undefined ? \"null\" : {}(v);\n}}\n\n",
+                        mangle(ty),
+                        self.serializer(inner)
+                    );
+                    continue;
+                }
+                VariableType::Tuple(fields) => {
+                    let items = fields
+                        .iter()
+                        .enumerate()
+                        .map(|(i, ty)| format!("{}(v[{i}])", self.serializer(ty)))
+                        .collect::<Vec<_>>();
+                    format!("[{}]", items.join(", "))
+                }
+                VariableType::Struct(_) | VariableType::Named(_) => {
+                    let items = struct_fields(ty)
+                        .into_iter()
+                        .map(|(name, ty)| {
+                            check_ident(name)?;
+                            Ok(format!("'\"{name}\":' + {}(v.{name})", self.serializer(ty)))
+                        })
+                        .collect::<anyhow::Result<Vec<_>>>()?;
+                    format!("[{}]", items.join(", "))
+                }
+                _ => continue,
+            };
+            let (open, close) = match ty {
+                VariableType::Struct(_) | VariableType::Named(_) => ("{", "}"),
+                _ => ("[", "]"),
+            };
+            out += &format!(
+                "function json_{}(v) {{\n    return \"{open}\" + {items}.join(\",\") + \"{close}\";\n}}\n\n",
+                mangle(ty)
+            );
+        }
+
+        Ok(out)
+    }
+
+    fn serializer(&self, ty: &VariableType) -> String {
+        format!("json_{}", mangle(ty))
+    }
+
+    fn stub(&self, functions: &[(&String, &FunctionConfig)]) -> anyhow::Result<String> {
+        let mut out = Vec::new();
+        for (name, func) in functions {
+            let params = param_names(func);
+            let mut doc = "/**\n".to_string();
+            for (param, ty) in params.iter().zip(&func.inputs) {
+                doc += &format!(" * @param {{{}}} {param}\n", self.type_name(ty)?);
+            }
+            doc += &format!(" * @returns {{{}}}\n */\n", self.type_name(&func.output)?);
+            out.push(format!(
+                "{doc}function {name}({}) {{\n    throw new Error(\"Not implemented\");\n}}\n",
+                params.join(", ")
+            ));
+        }
+        Ok(out.join("\n"))
+    }
+}
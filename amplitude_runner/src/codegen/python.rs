@@ -0,0 +1,199 @@
+use super::*;
+
+pub struct Python;
+
+impl Codegen for Python {
+    fn type_name(&self, ty: &VariableType) -> anyhow::Result<String> {
+        Ok(match ty {
+            VariableType::Int => "int".to_string(),
+            VariableType::Float => "float".to_string(),
+            VariableType::String | VariableType::Char => "str".to_string(),
+            VariableType::Boolean => "bool".to_string(),
+            VariableType::Array(inner) => format!("list[{}]", self.type_name(inner)?),
+            VariableType::Optional(inner) => format!("{} | None", self.type_name(inner)?),
+            VariableType::Map(key, value) => {
+                format!("dict[{}, {}]", self.type_name(key)?, self.type_name(value)?)
+            }
+            VariableType::Tuple(fields) => {
+                let fields = fields
+                    .iter()
+                    .map(|ty| self.type_name(ty))
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+                format!("tuple[{}]", fields.join(", "))
+            }
+            VariableType::Struct(_) => "dict".to_string(),
+            VariableType::Named(named) => named.name.clone(),
+        })
+    }
+
+    fn literal(&self, ty: &VariableType, value: &Value) -> anyhow::Result<String> {
+        Ok(match ty {
+            VariableType::Int => as_int(ty, value)?.to_string(),
+            VariableType::Float => {
+                let f = as_float(ty, value)?;
+                match f.is_finite() {
+                    true => float_literal(f),
+                    false => format!("float(\"{f}\")"),
+                }
+            }
+            // json strings are valid python strings
+            VariableType::String => serde_json::to_string(as_str(ty, value)?)?,
+            VariableType::Char => serde_json::to_string(&as_char(ty, value)?)?,
+            VariableType::Boolean => match as_bool(ty, value)? {
+                true => "True".to_string(),
+                false => "False".to_string(),
+            },
+            VariableType::Array(inner) => {
+                let items = as_array(ty, value)?
+                    .iter()
+                    .map(|v| self.literal(inner, v))
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+                format!("[{}]", items.join(", "))
+            }
+            VariableType::Optional(inner) => match value.is_null() {
+                true => "None".to_string(),
+                false => self.literal(inner, value)?,
+            },
+            VariableType::Map(key_ty, value_ty) => {
+                let items = map_entries(ty, value)?
+                    .into_iter()
+                    .map(|(k, v)| {
+                        Ok(format!(
+                            "{}: {}",
+                            self.literal(key_ty, k)?,
+                            self.literal(value_ty, v)?
+                        ))
+                    })
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+                format!("{{{}}}", items.join(", "))
+            }
+            VariableType::Tuple(fields) => {
+                let items = fields
+                    .iter()
+                    .zip(as_array(ty, value)?)
+                    .map(|(ty, v)| Ok(self.literal(ty, v)? + ","))
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+                format!("({})", items.join(" "))
+            }
+            VariableType::Struct(_) | VariableType::Named(_) => {
+                let items = struct_values(ty, value)?
+                    .into_iter()
+                    .map(|(name, ty, v)| {
+                        Ok(format!(
+                            "{}: {}",
+                            serde_json::to_string(name)?,
+                            self.literal(ty, v)?
+                        ))
+                    })
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+                format!("{{{}}}", items.join(", "))
+            }
+        })
+    }
+
+    fn type_defs(&self, types: &[VariableType]) -> anyhow::Result<String> {
+        let mut out = String::new();
+
+        // named types are dicts too, declared so they can be used in type hints
+        for ty in types {
+            let VariableType::Named(named) = ty else {
+                continue;
+            };
+            out += &format!("class {}(typing.TypedDict):\n", named.name);
+            for (name, ty) in struct_fields(ty) {
+                check_ident(name)?;
+                out += &format!("    {name}: {}\n", self.type_name(ty)?);
+            }
+            if named.fields.is_empty() {
+                out += "    pass\n";
+            }
+            out += "\n";
+        }
+
+        // serializers, so that floats are always written as floats
+        for ty in types {
+            let items = match ty {
+                VariableType::Array(inner) => {
+                    format!("{}(x) for x in v", self.serializer(inner))
+                }
+                VariableType::Map(key, value) => format!(
+                    "\"[\" + {}(k) + \",\" + {}(x) + \"]\" for k, x in v.items()",
+                    self.serializer(key),
+                    self.serializer(value)
+                ),
+                VariableType::Optional(inner) => {
+                    out += &format!(
+                        "def json_{}(v):\n    return \"null\" if v is None else {}(v)\n\n",
+                        mangle(ty),
+                        self.serializer(inner)
+                    );
+                    continue;
+                }
+                VariableType::Tuple(fields) => {
+                    let items = fields
+                        .iter()
+                        .enumerate()
+                        .map(|(i, ty)| format!("{}(v[{i}])", self.serializer(ty)))
+                        .collect::<Vec<_>>();
+                    format!("[{}]", items.join(", "))
+                }
+                VariableType::Struct(_) | VariableType::Named(_) => {
+                    let items = struct_fields(ty)
+                        .into_iter()
+                        .map(|(name, ty)| {
+                            check_ident(name)?;
+                            Ok(format!(
+                                "'\"{name}\":' + {}(v[\"{name}\"])",
+                                self.serializer(ty)
+                            ))
+                        })
+                        .collect::<anyhow::Result<Vec<_>>>()?;
+                    format!("[{}]", items.join(", "))
+                }
+                _ => continue,
+            };
+            let (open, close) = match ty {
+                VariableType::Struct(_) | VariableType::Named(_) => ("{", "}"),
+                _ => ("[", "]"),
+            };
+            out += &format!(
+                "def json_{}(v):\n    return \"{open}\" + \",\".join({items}) + \"{close}\"\n\n",
+                mangle(ty)
+            );
+        }
+
+        Ok(out)
+    }
+
+    fn serializer(&self, ty: &VariableType) -> String {
+        format!("json_{}", mangle(ty))
+    }
+
+    fn stub(&self, functions: &[(&String, &FunctionConfig)]) -> anyhow::Result<String> {
+        let mut out = Vec::new();
+        for (name, func) in functions {
+            let params = param_names(func)
+                .into_iter()
+                .zip(&func.inputs)
+                .map(|(param, ty)| Ok(format!("{param}: {}", self.type_name(ty)?)))
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            out.push(format!(
+                "def {name}({}) -> {}:\n    raise NotImplementedError\n",
+                params.join(", "),
+                self.type_name(&func.output)?
+            ));
+        }
+
+        // named types are declared by the runner, so hints using them can't be
+        // evaluated in the solution's file
+        let types = collect_types(
+            functions
+                .iter()
+                .flat_map(|(_, f)| f.inputs.iter().chain([&f.output])),
+        );
+        if types.iter().any(|ty| matches!(ty, VariableType::Named(_))) {
+            out.insert(0, "from __future__ import annotations\n".to_string());
+        }
+        Ok(out.join("\n\n"))
+    }
+}
@@ -0,0 +1,149 @@
+use super::*;
+
+pub struct Rust;
+
+impl Codegen for Rust {
+    fn type_name(&self, ty: &VariableType) -> anyhow::Result<String> {
+        Ok(match ty {
+            VariableType::Int => "i64".to_string(),
+            VariableType::Float => "f64".to_string(),
+            VariableType::String => "String".to_string(),
+            VariableType::Char => "char".to_string(),
+            VariableType::Boolean => "bool".to_string(),
+            VariableType::Array(inner) => format!("Vec<{}>", self.type_name(inner)?),
+            VariableType::Optional(inner) => format!("Option<{}>", self.type_name(inner)?),
+            // not imported, so the solution can import it without a conflict
+            VariableType::Map(key, value) => format!(
+                "std::collections::HashMap<{}, {}>",
+                self.type_name(key)?,
+                self.type_name(value)?
+            ),
+            VariableType::Tuple(fields) => {
+                let fields = fields
+                    .iter()
+                    .map(|ty| self.type_name(ty))
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+                match fields.len() {
+                    1 => format!("({},)", fields[0]),
+                    _ => format!("({})", fields.join(", ")),
+                }
+            }
+            VariableType::Struct(_) | VariableType::Named(_) => mangle(ty),
+        })
+    }
+
+    fn literal(&self, ty: &VariableType, value: &Value) -> anyhow::Result<String> {
+        Ok(match ty {
+            VariableType::Int => match as_int(ty, value)? {
+                i64::MIN => "i64::MIN".to_string(),
+                i => i.to_string(),
+            },
+            VariableType::Float => match as_float(ty, value)? {
+                f if f.is_finite() => float_literal(f),
+                f if f.is_nan() => "f64::NAN".to_string(),
+                f if f > 0.0 => "f64::INFINITY".to_string(),
+                _ => "f64::NEG_INFINITY".to_string(),
+            },
+            // `Debug` escapes strings the same way rust does
+            VariableType::String => format!("String::from({:?})", as_str(ty, value)?),
+            VariableType::Char => format!("{:?}", as_char(ty, value)?),
+            VariableType::Boolean => as_bool(ty, value)?.to_string(),
+            VariableType::Optional(inner) => match value.is_null() {
+                true => "None".to_string(),
+                false => format!("Some({})", self.literal(inner, value)?),
+            },
+            VariableType::Map(key_ty, value_ty) => {
+                let items = map_entries(ty, value)?
+                    .into_iter()
+                    .map(|(k, v)| {
+                        Ok(format!(
+                            "({}, {})",
+                            self.literal(key_ty, k)?,
+                            self.literal(value_ty, v)?
+                        ))
+                    })
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+                let name = self.type_name(ty)?.replacen('<', "::<", 1);
+                match items.is_empty() {
+                    true => format!("{name}::new()"),
+                    false => format!("{name}::from([{}])", items.join(", ")),
+                }
+            }
+            VariableType::Array(inner) => {
+                let items = as_array(ty, value)?
+                    .iter()
+                    .map(|v| self.literal(inner, v))
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+                format!("vec![{}]", items.join(", "))
+            }
+            VariableType::Tuple(fields) => {
+                let items = fields
+                    .iter()
+                    .zip(as_array(ty, value)?)
+                    .map(|(ty, v)| self.literal(ty, v))
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+                match items.len() {
+                    1 => format!("({},)", items[0]),
+                    _ => format!("({})", items.join(", ")),
+                }
+            }
+            VariableType::Struct(_) | VariableType::Named(_) => {
+                let items = struct_values(ty, value)?
+                    .into_iter()
+                    .map(|(name, ty, v)| Ok(format!("{name}: {}", self.literal(ty, v)?)))
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+                format!("{} {{ {} }}", mangle(ty), items.join(", "))
+            }
+        })
+    }
+
+    fn type_defs(&self, types: &[VariableType]) -> anyhow::Result<String> {
+        let mut out = String::new();
+
+        // everything else implements `ToJson` in the prelude
+        for ty in types
+            .iter()
+            .filter(|ty| matches!(ty, VariableType::Struct(_) | VariableType::Named(_)))
+        {
+            let name = mangle(ty);
+            let fields = struct_fields(ty);
+
+            out += "#[derive(Debug, Clone, PartialEq)]\n";
+            out += &format!("pub struct {name} {{\n");
+            for (field, ty) in &fields {
+                check_ident(field)?;
+                out += &format!("    pub {field}: {},\n", self.type_name(ty)?);
+            }
+            out += "}\n\n";
+
+            out += &format!("impl ToJson for {name} {{\n");
+            out += "    fn to_json(&self) -> String {\n";
+            out += "        let mut out = String::from(\"{\");\n";
+            for (i, (field, _)) in fields.iter().enumerate() {
+                let comma = if i == 0 { "" } else { "," };
+                out += &format!("        out += \"{comma}\\\"{field}\\\":\";\n");
+                out += &format!("        out += &self.{field}.to_json();\n");
+            }
+            out += "        out + \"}\"\n    }\n}\n\n";
+        }
+
+        Ok(out)
+    }
+
+    fn stub(&self, functions: &[(&String, &FunctionConfig)]) -> anyhow::Result<String> {
+        let mut out = Vec::new();
+        for (name, func) in functions {
+            let params = param_names(func)
+                .into_iter()
+                .zip(&func.inputs)
+                .map(|(param, ty)| Ok(format!("{param}: {}", self.type_name(ty)?)))
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            out.push(format!(
+                "fn {name}({}) -> {} {{\n    todo!()\n}}\n",
+                params.join(", "),
+                self.type_name(&func.output)?
+            ));
+        }
+        Ok(out.join("\n"))
+    }
+}
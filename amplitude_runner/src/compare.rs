@@ -0,0 +1,368 @@
+//! Checking a function's output against the expected output of a test case.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{codegen::struct_fields, var_type::VariableType};
+
+/// How the output of a function is compared to the expected output.
+///
+/// Comparators are applied all the way through the output type, so
+/// `unordered` on a `(int[], int[][])` ignores the order of every array in it,
+/// and `float` allows an error in every float.
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Comparator {
+    /// Values have to be equal (ints and floats are compared as numbers)
+    #[default]
+    Exact,
+    /// Floats can be off by `abs_epsilon`, or by `rel_epsilon` times the
+    /// larger of the two values
+    Float {
+        #[serde(default)]
+        abs_epsilon: f64,
+        #[serde(default)]
+        rel_epsilon: f64,
+    },
+    /// Arrays can be in any order, but have to contain the same elements the
+    /// same number of times
+    Unordered,
+    /// Arrays can be in any order, and duplicates are ignored
+    Set,
+    /// Strings are compared ignoring case
+    CaseInsensitive,
+    /// Strings are compared ignoring leading and trailing whitespace, and
+    /// treating any run of whitespace as a single space
+    Whitespace,
+}
+
+impl Comparator {
+    /// Make sure the comparator makes sense for a function returning `ty`
+    pub fn check(&self, ty: &VariableType) -> anyhow::Result<()> {
+        let contains = |f: fn(&VariableType) -> bool| contains_type(ty, f);
+        match self {
+            Comparator::Exact => {}
+            Comparator::Float {
+                abs_epsilon,
+                rel_epsilon,
+            } => {
+                anyhow::ensure!(
+                    *abs_epsilon >= 0.0 && *rel_epsilon >= 0.0,
+                    "Epsilons can't be negative"
+                );
+                anyhow::ensure!(
+                    contains(|ty| matches!(ty, VariableType::Float)),
+                    "`float` comparator used on `{ty}`, which doesn't contain any floats"
+                );
+            }
+            Comparator::Unordered | Comparator::Set => anyhow::ensure!(
+                contains(|ty| matches!(ty, VariableType::Array(_))),
+                "Array comparator used on `{ty}`, which doesn't contain any arrays"
+            ),
+            Comparator::CaseInsensitive | Comparator::Whitespace => anyhow::ensure!(
+                contains(|ty| matches!(ty, VariableType::String | VariableType::Char)),
+                "String comparator used on `{ty}`, which doesn't contain any strings"
+            ),
+        }
+        Ok(())
+    }
+
+    /// Whether `actual` is a correct output of type `ty`, when `expected` was
+    /// expected
+    pub fn matches(&self, ty: &VariableType, expected: &Value, actual: &Value) -> bool {
+        match ty {
+            VariableType::Int => match (expected.as_i64(), actual.as_i64()) {
+                (Some(e), Some(a)) => e == a,
+                _ => expected == actual,
+            },
+            // non-finite floats are serialized as `null`
+            VariableType::Float => match (expected.as_f64(), actual.as_f64()) {
+                (Some(e), Some(a)) => self.float_matches(e, a),
+                _ => expected.is_null() && actual.is_null(),
+            },
+            VariableType::String | VariableType::Char => match (expected.as_str(), actual.as_str())
+            {
+                (Some(e), Some(a)) => match self {
+                    Comparator::CaseInsensitive => e.to_lowercase() == a.to_lowercase(),
+                    Comparator::Whitespace => e.split_whitespace().eq(a.split_whitespace()),
+                    _ => e == a,
+                },
+                _ => false,
+            },
+            VariableType::Boolean => expected.is_boolean() && expected == actual,
+            VariableType::Optional(inner) => match (expected.is_null(), actual.is_null()) {
+                (true, true) => true,
+                (false, false) => self.matches(inner, expected, actual),
+                _ => false,
+            },
+            // entries can be in any order, keys are always compared exactly
+            VariableType::Map(key, value) => {
+                let (Some(expected), Some(actual)) = (expected.as_array(), actual.as_array())
+                else {
+                    return false;
+                };
+                unordered_eq(expected, actual, |e, a| {
+                    let (Some([ek, ev]), Some([ak, av])) =
+                        (e.as_array().map(|x| &x[..]), a.as_array().map(|x| &x[..]))
+                    else {
+                        return false;
+                    };
+                    Comparator::Exact.matches(key, ek, ak) && self.matches(value, ev, av)
+                })
+            }
+            VariableType::Array(inner) => {
+                let (Some(expected), Some(actual)) = (expected.as_array(), actual.as_array())
+                else {
+                    return false;
+                };
+                let eq = |e: &Value, a: &Value| self.matches(inner, e, a);
+                match self {
+                    Comparator::Unordered => unordered_eq(expected, actual, eq),
+                    Comparator::Set => {
+                        expected.iter().all(|e| actual.iter().any(|a| eq(e, a)))
+                            && actual.iter().all(|a| expected.iter().any(|e| eq(e, a)))
+                    }
+                    _ => {
+                        expected.len() == actual.len()
+                            && expected.iter().zip(actual).all(|(e, a)| eq(e, a))
+                    }
+                }
+            }
+            VariableType::Tuple(fields) => {
+                let (Some(expected), Some(actual)) = (expected.as_array(), actual.as_array())
+                else {
+                    return false;
+                };
+                expected.len() == fields.len()
+                    && actual.len() == fields.len()
+                    && fields
+                        .iter()
+                        .zip(expected.iter().zip(actual))
+                        .all(|(ty, (e, a))| self.matches(ty, e, a))
+            }
+            VariableType::Struct(_) | VariableType::Named(_) => {
+                let (Some(expected), Some(actual)) = (expected.as_object(), actual.as_object())
+                else {
+                    return false;
+                };
+                let fields = struct_fields(ty);
+                actual.len() == fields.len()
+                    && fields.into_iter().all(|(name, ty)| {
+                        match (expected.get(name), actual.get(name)) {
+                            (Some(e), Some(a)) => self.matches(ty, e, a),
+                            _ => false,
+                        }
+                    })
+            }
+        }
+    }
+
+    fn float_matches(&self, expected: f64, actual: f64) -> bool {
+        match *self {
+            Comparator::Float {
+                abs_epsilon,
+                rel_epsilon,
+            } => {
+                let diff = (expected - actual).abs();
+                expected == actual
+                    || diff <= abs_epsilon
+                    || diff <= rel_epsilon * expected.abs().max(actual.abs())
+            }
+            _ => expected == actual,
+        }
+    }
+}
+
+/// Whether every element of `expected` can be paired with a different element
+/// of `actual`. A tolerant `eq` can match an element with more than one other,
+/// so taking the first match isn't enough, the pairs are found with augmenting
+/// paths instead.
+fn unordered_eq(expected: &[Value], actual: &[Value], eq: impl Fn(&Value, &Value) -> bool) -> bool {
+    if expected.len() != actual.len() {
+        return false;
+    }
+    // the elements of `actual` each element of `expected` matches
+    let matches = expected
+        .iter()
+        .map(|e| {
+            (0..actual.len())
+                .filter(|&i| eq(e, &actual[i]))
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    // the element of `expected` each element of `actual` is paired with
+    let mut paired = vec![None; actual.len()];
+    (0..expected.len()).all(|e| augment(e, &matches, &mut paired, &mut vec![false; actual.len()]))
+}
+
+/// Pair `e` with one of its matches, pairing whatever had it with another of
+/// its own matches if needed
+fn augment(
+    e: usize,
+    matches: &[Vec<usize>],
+    paired: &mut [Option<usize>],
+    seen: &mut [bool],
+) -> bool {
+    for &a in &matches[e] {
+        if seen[a] {
+            continue;
+        }
+        seen[a] = true;
+        let free = match paired[a] {
+            Some(other) => augment(other, matches, paired, seen),
+            None => true,
+        };
+        if free {
+            paired[a] = Some(e);
+            return true;
+        }
+    }
+    false
+}
+
+fn contains_type(ty: &VariableType, f: fn(&VariableType) -> bool) -> bool {
+    f(ty)
+        || match ty {
+            VariableType::Array(inner) | VariableType::Optional(inner) => contains_type(inner, f),
+            VariableType::Map(key, value) => contains_type(key, f) || contains_type(value, f),
+            VariableType::Tuple(fields) => fields.iter().any(|ty| contains_type(ty, f)),
+            VariableType::Struct(_) | VariableType::Named(_) => struct_fields(ty)
+                .into_iter()
+                .any(|(_, ty)| contains_type(ty, f)),
+            _ => false,
+        }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::*;
+
+    fn ty(s: &str) -> VariableType {
+        VariableType::try_from(s).unwrap()
+    }
+
+    #[test]
+    fn test_exact() {
+        let cmp = Comparator::Exact;
+        assert!(cmp.matches(&ty("float"), &json!(1), &json!(1.0)));
+        assert!(cmp.matches(&ty("float"), &json!(null), &json!(null)));
+        assert!(!cmp.matches(&ty("float"), &json!(0.3), &json!(0.1 + 0.2)));
+        assert!(!cmp.matches(&ty("int[]"), &json!([1, 2]), &json!([2, 1])));
+        assert!(!cmp.matches(&ty("int[]"), &json!([1, 2]), &json!([1, 2, 3])));
+        assert!(cmp.matches(
+            &ty("{a: int, b: string}"),
+            &json!({"a": 1, "b": "x"}),
+            &json!({"b": "x", "a": 1})
+        ));
+    }
+
+    #[test]
+    fn test_float() {
+        let abs = Comparator::Float {
+            abs_epsilon: 1e-6,
+            rel_epsilon: 0.0,
+        };
+        assert!(abs.matches(&ty("float"), &json!(0.3), &json!(0.1 + 0.2)));
+        assert!(!abs.matches(&ty("float"), &json!(0.3), &json!(0.31)));
+        // applied to every float in the output
+        assert!(abs.matches(
+            &ty("(int, float[])"),
+            &json!([1, [0.3, 1.0]]),
+            &json!([1, [0.1 + 0.2, 1.0000001]])
+        ));
+
+        let rel = Comparator::Float {
+            abs_epsilon: 0.0,
+            rel_epsilon: 1e-3,
+        };
+        assert!(rel.matches(&ty("float"), &json!(1e9), &json!(1.0005e9)));
+        assert!(!rel.matches(&ty("float"), &json!(1.0), &json!(1.01)));
+    }
+
+    #[test]
+    fn test_unordered() {
+        let cmp = Comparator::Unordered;
+        assert!(cmp.matches(&ty("int[][]"), &json!([[1, 2], [3]]), &json!([[3], [2, 1]])));
+        assert!(!cmp.matches(&ty("int[]"), &json!([1, 1, 2]), &json!([1, 2, 2])));
+        // 1.05 matches both, so it can't just take the first one
+        let close = |e: &Value, a: &Value| {
+            let float = Comparator::Float {
+                abs_epsilon: 0.1,
+                rel_epsilon: 0.0,
+            };
+            float.matches(&ty("float"), e, a)
+        };
+        assert!(unordered_eq(
+            &[json!(1.0), json!(1.1)],
+            &[json!(1.05), json!(0.96)],
+            close
+        ));
+        assert!(!unordered_eq(
+            &[json!(1.0), json!(1.1)],
+            &[json!(1.05), json!(1.3)],
+            close
+        ));
+
+        let cmp = Comparator::Set;
+        assert!(cmp.matches(&ty("int[]"), &json!([1, 1, 2]), &json!([2, 1])));
+        assert!(!cmp.matches(&ty("int[]"), &json!([1, 2]), &json!([1, 2, 3])));
+    }
+
+    #[test]
+    fn test_strings() {
+        assert!(Comparator::CaseInsensitive.matches(
+            &ty("string[]"),
+            &json!(["Hello"]),
+            &json!(["hELLO"])
+        ));
+        assert!(Comparator::Whitespace.matches(
+            &ty("string"),
+            &json!("a b\nc"),
+            &json!("  a   b c\n")
+        ));
+        assert!(!Comparator::Whitespace.matches(&ty("string"), &json!("ab"), &json!("a b")));
+    }
+
+    #[test]
+    fn test_optional_map() {
+        let cmp = Comparator::Exact;
+        assert!(cmp.matches(&ty("int?"), &json!(null), &json!(null)));
+        assert!(!cmp.matches(&ty("int?"), &json!(null), &json!(0)));
+        assert!(cmp.matches(
+            &ty("map<string, int>"),
+            &json!([["a", 1], ["b", 2]]),
+            &json!([["b", 2], ["a", 1]])
+        ));
+        assert!(!cmp.matches(
+            &ty("map<string, int>"),
+            &json!([["a", 1], ["b", 2]]),
+            &json!([["a", 2], ["b", 1]])
+        ));
+        // keys are compared exactly, values with the comparator
+        let cmp = Comparator::CaseInsensitive;
+        assert!(cmp.matches(
+            &ty("map<string, string>"),
+            &json!([["a", "X"]]),
+            &json!([["a", "x"]])
+        ));
+        assert!(!cmp.matches(
+            &ty("map<string, string>"),
+            &json!([["a", "X"]]),
+            &json!([["A", "X"]])
+        ));
+    }
+
+    #[test]
+    fn test_check() {
+        assert!(Comparator::Unordered.check(&ty("(int, int[])")).is_ok());
+        assert!(Comparator::Unordered.check(&ty("(int, int)")).is_err());
+        assert!(Comparator::CaseInsensitive.check(&ty("int")).is_err());
+        let negative = Comparator::Float {
+            abs_epsilon: -1.0,
+            rel_epsilon: 0.0,
+        };
+        assert!(negative.check(&ty("float")).is_err());
+    }
+}
@@ -0,0 +1,604 @@
+//! Test cases generated from constraints on the inputs of each function, for
+//! exercises that don't need a generator program. The inputs are random
+//! values of each input's type, and the outputs come from running a reference
+//! solution.
+//!
+//! ```toml
+//! [functions.sum]
+//! inputs = ["int[]", "string"]
+//! output = "int"
+//!
+//! [[functions.sum.constraints]]
+//! len = [1, 20]
+//! sorted = true
+//! items = { min = 0, max = 1000 }
+//!
+//! [[functions.sum.constraints]]
+//! alphabet = "ab"
+//! ```
+
+use std::{cmp::Ordering, collections::HashMap};
+
+use amplitude_common::config::Config;
+use anyhow::Context;
+use rand::{seq::SliceRandom, Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+use serde_json::{Number, Value};
+
+use crate::{
+    cache,
+    codegen::struct_fields,
+    exercise::{runner_template, with_fixtures, ExerciseConfig, TestCase, TestOutput, TestResult},
+    lang::Language,
+    runner::{run, RunOutput},
+    var_type::VariableType,
+};
+
+/// Constraints on the random values of an input. Everything is optional, and
+/// only options that make sense for the input's type can be set. The
+/// constraints of optionals apply to their value.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Constraint {
+    /// The smallest value of ints and floats, -100 by default
+    pub min: Option<Number>,
+    /// The largest value of ints and floats, 100 by default
+    pub max: Option<Number>,
+    /// The inclusive bounds of the length of strings, arrays and maps, 0 to 10
+    /// by default
+    pub len: Option<[usize; 2]>,
+    /// The characters of strings and chars, lowercase ascii letters by default
+    pub alphabet: Option<String>,
+    /// Sort arrays in ascending order
+    #[serde(default)]
+    pub sorted: bool,
+    /// Make the items of arrays all different
+    #[serde(default)]
+    pub unique: bool,
+    /// The chance of optionals being `null`, 0.2 by default
+    pub null_chance: Option<f64>,
+    /// Constraints on the items of arrays and the values of maps
+    pub items: Option<Box<Constraint>>,
+    /// Constraints on the keys of maps
+    pub keys: Option<Box<Constraint>>,
+    /// Constraints on the fields of structs by name, or of tuples by index
+    #[serde(default)]
+    pub fields: HashMap<String, Constraint>,
+}
+
+const DEFAULT_RANGE: (i64, i64) = (-100, 100);
+const DEFAULT_LEN: [usize; 2] = [0, 10];
+const DEFAULT_ALPHABET: &str = "abcdefghijklmnopqrstuvwxyz";
+const DEFAULT_NULL_CHANCE: f64 = 0.2;
+/// How many values are tried for each item of a `unique` array or map before
+/// giving up
+const UNIQUE_ATTEMPTS: usize = 100;
+
+impl Constraint {
+    /// Make sure the constraint can be used on type `ty`
+    pub fn check(&self, ty: &VariableType) -> anyhow::Result<()> {
+        if let VariableType::Optional(inner) = ty {
+            if let Some(chance) = self.null_chance {
+                anyhow::ensure!(
+                    (0.0..=1.0).contains(&chance),
+                    "`null_chance` has to be between 0 and 1"
+                );
+            }
+            return Constraint {
+                null_chance: None,
+                ..self.clone()
+            }
+            .check(inner);
+        }
+
+        let allowed: &[&str] = match ty {
+            VariableType::Int | VariableType::Float => &["min", "max"],
+            VariableType::String => &["len", "alphabet"],
+            VariableType::Char => &["alphabet"],
+            VariableType::Boolean => &[],
+            VariableType::Array(_) => &["len", "sorted", "unique", "items"],
+            VariableType::Map(..) => &["len", "keys", "items"],
+            VariableType::Tuple(_) | VariableType::Struct(_) | VariableType::Named(_) => {
+                &["fields"]
+            }
+            VariableType::Optional(_) => unreachable!(),
+        };
+        let set = [
+            ("min", self.min.is_some()),
+            ("max", self.max.is_some()),
+            ("len", self.len.is_some()),
+            ("alphabet", self.alphabet.is_some()),
+            ("sorted", self.sorted),
+            ("unique", self.unique),
+            ("null_chance", self.null_chance.is_some()),
+            ("items", self.items.is_some()),
+            ("keys", self.keys.is_some()),
+            ("fields", !self.fields.is_empty()),
+        ];
+        for (option, _) in set.iter().filter(|(_, set)| *set) {
+            anyhow::ensure!(
+                allowed.contains(option),
+                "`{option}` can't be used on `{ty}`"
+            );
+        }
+
+        match ty {
+            VariableType::Int => {
+                let (min, max) = self.int_range()?;
+                anyhow::ensure!(min <= max, "`min` is larger than `max`");
+            }
+            VariableType::Float => {
+                let (min, max) = self.float_range()?;
+                anyhow::ensure!(min <= max, "`min` is larger than `max`");
+            }
+            VariableType::String | VariableType::Char => {
+                if let Some(alphabet) = &self.alphabet {
+                    anyhow::ensure!(!alphabet.is_empty(), "`alphabet` is empty");
+                }
+            }
+            VariableType::Array(inner) => {
+                anyhow::ensure!(
+                    !self.sorted || is_sortable(inner),
+                    "Arrays of `{inner}` can't be `sorted`"
+                );
+                if let Some(items) = &self.items {
+                    items.check(inner).context("In `items`")?;
+                }
+            }
+            VariableType::Map(key, value) => {
+                if let Some(keys) = &self.keys {
+                    keys.check(key).context("In `keys`")?;
+                }
+                if let Some(items) = &self.items {
+                    items.check(value).context("In `items`")?;
+                }
+            }
+            VariableType::Tuple(fields) => {
+                for (index, constraint) in &self.fields {
+                    let ty = index
+                        .parse::<usize>()
+                        .ok()
+                        .and_then(|i| fields.get(i))
+                        .with_context(|| format!("`{ty}` has no field `{index}`"))?;
+                    constraint
+                        .check(ty)
+                        .with_context(|| format!("In field `{index}`"))?;
+                }
+            }
+            VariableType::Struct(_) | VariableType::Named(_) => {
+                let fields = struct_fields(ty);
+                for (name, constraint) in &self.fields {
+                    let (_, ty) = fields
+                        .iter()
+                        .find(|(field, _)| *field == name)
+                        .with_context(|| format!("`{ty}` has no field `{name}`"))?;
+                    constraint
+                        .check(ty)
+                        .with_context(|| format!("In field `{name}`"))?;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn int_range(&self) -> anyhow::Result<(i64, i64)> {
+        let bound = |x: &Option<Number>, default| match x {
+            Some(x) => x
+                .as_i64()
+                .with_context(|| format!("Expected an int bound, found `{x}`")),
+            None => Ok(default),
+        };
+        Ok((
+            bound(&self.min, DEFAULT_RANGE.0)?,
+            bound(&self.max, DEFAULT_RANGE.1)?,
+        ))
+    }
+
+    fn float_range(&self) -> anyhow::Result<(f64, f64)> {
+        let bound = |x: &Option<Number>, default: i64| match x {
+            Some(x) => x.as_f64().context("Expected a float bound"),
+            None => Ok(default as f64),
+        };
+        Ok((
+            bound(&self.min, DEFAULT_RANGE.0)?,
+            bound(&self.max, DEFAULT_RANGE.1)?,
+        ))
+    }
+
+    fn len(&self, rng: &mut ChaCha8Rng) -> anyhow::Result<usize> {
+        let [min, max] = self.len.unwrap_or(DEFAULT_LEN);
+        anyhow::ensure!(
+            min <= max,
+            "The first bound of `len` is larger than the second"
+        );
+        Ok(rng.gen_range(min..=max))
+    }
+
+    fn alphabet(&self) -> Vec<char> {
+        self.alphabet
+            .as_deref()
+            .unwrap_or(DEFAULT_ALPHABET)
+            .chars()
+            .collect()
+    }
+
+    /// A random value of type `ty`
+    pub fn generate(&self, ty: &VariableType, rng: &mut ChaCha8Rng) -> anyhow::Result<Value> {
+        let default = Constraint::default();
+
+        Ok(match ty {
+            VariableType::Int => {
+                let (min, max) = self.int_range()?;
+                Value::from(rng.gen_range(min..=max))
+            }
+            VariableType::Float => {
+                let (min, max) = self.float_range()?;
+                Value::from(rng.gen_range(min..=max))
+            }
+            VariableType::String => {
+                let alphabet = self.alphabet();
+                let len = self.len(rng)?;
+                let s = (0..len)
+                    .map(|_| *alphabet.choose(rng).unwrap())
+                    .collect::<String>();
+                Value::from(s)
+            }
+            VariableType::Char => Value::from(self.alphabet().choose(rng).unwrap().to_string()),
+            VariableType::Boolean => Value::from(rng.gen_bool(0.5)),
+            VariableType::Optional(inner) => {
+                match rng.gen_bool(self.null_chance.unwrap_or(DEFAULT_NULL_CHANCE)) {
+                    true => Value::Null,
+                    false => self.generate(inner, rng)?,
+                }
+            }
+            VariableType::Array(inner) => {
+                let len = self.len(rng)?;
+                let items = self.items.as_deref().unwrap_or(&default);
+                let mut out = match self.unique {
+                    true => unique_values(len, rng, |rng| items.generate(inner, rng))
+                        .with_context(|| format!("While generating `{ty}`"))?,
+                    false => (0..len)
+                        .map(|_| items.generate(inner, rng))
+                        .collect::<anyhow::Result<_>>()?,
+                };
+                if self.sorted {
+                    out.sort_by(compare_values);
+                }
+                Value::from(out)
+            }
+            VariableType::Map(key, value) => {
+                let len = self.len(rng)?;
+                let (keys, items) = (
+                    self.keys.as_deref().unwrap_or(&default),
+                    self.items.as_deref().unwrap_or(&default),
+                );
+                let keys = unique_values(len, rng, |rng| keys.generate(key, rng))
+                    .with_context(|| format!("While generating `{ty}`"))?;
+                let pairs = keys
+                    .into_iter()
+                    .map(|k| Ok(Value::from(vec![k, items.generate(value, rng)?])))
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+                Value::from(pairs)
+            }
+            VariableType::Tuple(fields) => {
+                let items = fields
+                    .iter()
+                    .enumerate()
+                    .map(|(i, ty)| {
+                        let constraint = self.fields.get(&i.to_string()).unwrap_or(&default);
+                        constraint.generate(ty, rng)
+                    })
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+                Value::from(items)
+            }
+            VariableType::Struct(_) | VariableType::Named(_) => {
+                let object = struct_fields(ty)
+                    .into_iter()
+                    .map(|(name, ty)| {
+                        let constraint = self.fields.get(name).unwrap_or(&default);
+                        Ok((name.clone(), constraint.generate(ty, rng)?))
+                    })
+                    .collect::<anyhow::Result<serde_json::Map<_, _>>>()?;
+                Value::from(object)
+            }
+        })
+    }
+}
+
+/// `len` different values from `generate`
+fn unique_values(
+    len: usize,
+    rng: &mut ChaCha8Rng,
+    mut generate: impl FnMut(&mut ChaCha8Rng) -> anyhow::Result<Value>,
+) -> anyhow::Result<Vec<Value>> {
+    let mut out = Vec::with_capacity(len);
+    while out.len() < len {
+        let mut attempts = 0;
+        let value = loop {
+            let value = generate(rng)?;
+            if !out.contains(&value) {
+                break value;
+            }
+            attempts += 1;
+            anyhow::ensure!(
+                attempts < UNIQUE_ATTEMPTS,
+                "Couldn't find {len} different values, the constraints don't allow enough of them"
+            );
+        };
+        out.push(value);
+    }
+    Ok(out)
+}
+
+fn is_sortable(ty: &VariableType) -> bool {
+    matches!(
+        ty,
+        VariableType::Int
+            | VariableType::Float
+            | VariableType::String
+            | VariableType::Char
+            | VariableType::Boolean
+    )
+}
+
+/// The order of two values of a type that [`is_sortable`]
+fn compare_values(a: &Value, b: &Value) -> Ordering {
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => match (a.as_i64(), b.as_i64()) {
+            (Some(a), Some(b)) => a.cmp(&b),
+            _ => a
+                .as_f64()
+                .partial_cmp(&b.as_f64())
+                .unwrap_or(Ordering::Equal),
+        },
+        (Value::String(a), Value::String(b)) => a.cmp(b),
+        (Value::Bool(a), Value::Bool(b)) => a.cmp(b),
+        _ => Ordering::Equal,
+    }
+}
+
+/// Generate the test cases of every function from its constraints, with the
+/// outputs of `solution`, a reference solution in `lang`. Cached like the
+/// test cases of generators, see [`cache::generate_cached`].
+pub fn generate_from_constraints(
+    lang: &Language,
+    cfg: &Config,
+    solution: &str,
+    config_src: &str,
+    exercise_cfg: &mut ExerciseConfig,
+) -> anyhow::Result<()> {
+    for (name, func) in exercise_cfg.functions.iter_mut() {
+        let constraints = func
+            .constraints
+            .as_ref()
+            .with_context(|| format!("`{name}` has no `constraints`, and there is no generator"))?;
+        let mut rng = ChaCha8Rng::seed_from_u64(func.seed);
+        let count = (func.visible_cases + func.hidden_cases) as usize;
+
+        let mut tests = Vec::with_capacity(count);
+        for i in 0..count {
+            let inputs = func
+                .inputs
+                .iter()
+                .zip(constraints)
+                .map(|(ty, constraint)| constraint.generate(ty, &mut rng))
+                .collect::<anyhow::Result<_>>()
+                .with_context(|| format!("While generating inputs for `{name}`"))?;
+            tests.push(TestCase {
+                inputs,
+                output: Value::Null,
+                hidden: i >= func.visible_cases as usize,
+            });
+        }
+        func.tests = tests;
+    }
+
+    let runner = runner_template(lang, exercise_cfg, SOLUTION_ID)
+        .context("While generating runner template")?;
+    cache::load_or_generate(
+        lang,
+        cfg,
+        config_src,
+        &[&runner, solution],
+        exercise_cfg,
+        |exercise_cfg| run_solution(lang, cfg, &runner, solution, exercise_cfg),
+    )
+}
+
+/// The name of the reference solution's file for runners
+const SOLUTION_ID: &str = "solution";
+
+/// Run the reference solution on the test cases of `exercise_cfg`, using its
+/// outputs as the expected ones
+fn run_solution(
+    lang: &Language,
+    cfg: &Config,
+    runner: &str,
+    solution: &str,
+    exercise_cfg: &ExerciseConfig,
+) -> anyhow::Result<HashMap<String, Vec<TestCase>>> {
+    let output = run(
+        cfg.docker.language_config.get(lang.image()).unwrap(),
+        &cfg.docker,
+        runner,
+        with_fixtures(
+            exercise_cfg,
+            [(lang.code_path(SOLUTION_ID), solution.as_bytes())],
+        ),
+        "",
+    )
+    .context("While running reference solution")?;
+    if let Some(verdict) = TestResult::from_run_output(&output) {
+        anyhow::bail!("The reference solution failed: {verdict:?}");
+    }
+
+    let RunOutput { stdout, stderr, .. } = output;
+    let outputs: HashMap<String, Vec<TestOutput>> =
+        serde_json::from_str(&stdout).with_context(|| {
+            format!("While parsing reference outputs\nstdout:\n{stdout}\n\nstderr:\n{stderr}")
+        })?;
+
+    let mut test_cases = HashMap::new();
+    for (func, outputs) in outputs {
+        let tests = &exercise_cfg
+            .functions
+            .get(&func)
+            .with_context(|| {
+                format!("The reference solution printed outputs for an unknown function `{func}`")
+            })?
+            .tests;
+        anyhow::ensure!(
+            outputs.len() == tests.len(),
+            "The reference solution printed {} outputs for `{func}`, which has {} test cases",
+            outputs.len(),
+            tests.len()
+        );
+        let tests = tests
+            .iter()
+            .zip(outputs)
+            .enumerate()
+            .map(|(i, (test, output))| match output {
+                TestOutput::Answer { value, .. } => Ok(TestCase {
+                    output: value,
+                    ..test.clone()
+                }),
+                TestOutput::Exception { traceback, .. } => Err(anyhow::anyhow!(
+                    "The reference solution threw an exception on test case {i} of \
+                     `{func}`:\n{traceback}"
+                )),
+            })
+            .collect::<anyhow::Result<_>>()?;
+        test_cases.insert(func, tests);
+    }
+    Ok(test_cases)
+}
+
+#[cfg(test)]
+mod test {
+    use amplitude_common::config_and_set_path;
+    use serde_json::json;
+
+    use super::*;
+
+    fn ty(s: &str) -> VariableType {
+        VariableType::try_from(s).unwrap()
+    }
+
+    fn constraint(s: &str) -> Constraint {
+        toml::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn test_check() {
+        let check = |s, t| constraint(s).check(&ty(t));
+        assert!(check("min = 0\nmax = 5", "int").is_ok());
+        assert!(check("min = 0.5", "int").is_err());
+        assert!(check("min = 5\nmax = 0", "float").is_err());
+        assert!(check("len = [1, 2]", "int").is_err());
+        assert!(check("null_chance = 0.5\nmin = 0", "int?").is_ok());
+        assert!(check("sorted = true\nitems = { min = 0 }", "int[]").is_ok());
+        assert!(check("sorted = true", "int[][]").is_err());
+        assert!(check("fields = { x = { alphabet = \"ab\" } }", "{x: string}").is_ok());
+        assert!(check("fields = { y = {} }", "{x: string}").is_err());
+        assert!(check("fields = { 1 = { min = 0 } }", "(bool, int)").is_ok());
+        assert!(check("keys = { min = 0 }", "map<int, string>").is_ok());
+    }
+
+    #[test]
+    fn test_generate() {
+        let generate = |s, t| {
+            let (constraint, ty) = (constraint(s), ty(t));
+            constraint.check(&ty).unwrap();
+            let value = constraint
+                .generate(&ty, &mut ChaCha8Rng::seed_from_u64(0))
+                .unwrap();
+            ty.check_value(&value).unwrap();
+            value
+        };
+
+        let value = generate(
+            "len = [20, 20]\nsorted = true\nunique = true\nitems = { min = 0, max = 30 }",
+            "int[]",
+        );
+        let items = value.as_array().unwrap();
+        assert_eq!(items.len(), 20);
+        assert!(items.windows(2).all(|x| x[0].as_i64() < x[1].as_i64()));
+        assert!(items
+            .iter()
+            .all(|x| (0..=30).contains(&x.as_i64().unwrap())));
+
+        let value = generate("len = [5, 5]\nalphabet = \"x\"", "string");
+        assert_eq!(value, json!("xxxxx"));
+        let value = generate("null_chance = 1.0", "int?");
+        assert_eq!(value, json!(null));
+        generate("len = [3, 3]", "map<int, {x: char}[]?>");
+
+        // the same seed gives the same values
+        assert_eq!(
+            generate("", "(int, string)[]"),
+            generate("", "(int, string)[]")
+        );
+
+        // there aren't 3 different bools
+        let constraint = constraint("len = [3, 3]");
+        assert!(constraint
+            .generate(&ty("map<bool, int>"), &mut ChaCha8Rng::seed_from_u64(0))
+            .is_err());
+    }
+
+    #[test]
+    fn test_generate_from_constraints() {
+        let cfg = config_and_set_path().unwrap();
+        let config_src = r#"
+            title = "test"
+            [functions.total]
+            inputs = ["int[]"]
+            output = "int"
+            visible_cases = 2
+            hidden_cases = 1
+            [[functions.total.constraints]]
+            items = { min = 0, max = 10 }
+            "#;
+        let mut exercise_cfg: ExerciseConfig = toml::from_str(config_src).unwrap();
+        exercise_cfg.check().unwrap();
+
+        generate_from_constraints(
+            &Language::Python,
+            &cfg,
+            "def total(xs):\n    return sum(xs)\n",
+            config_src,
+            &mut exercise_cfg,
+        )
+        .unwrap();
+        let tests = &exercise_cfg.functions["total"].tests;
+        assert_eq!(tests.len(), 3);
+        assert!(tests[2].hidden);
+        for test in tests {
+            let sum = test.inputs[0]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|x| x.as_i64().unwrap())
+                .sum::<i64>();
+            assert_eq!(test.output, json!(sum));
+        }
+
+        // outputs printed before the harness runs
+        let forged = |outputs: &str| {
+            let solution = format!("import sys\nprint('{outputs}')\nsys.exit(0)\n");
+            let err = generate_from_constraints(
+                &Language::Python,
+                &cfg,
+                &solution,
+                config_src,
+                &mut exercise_cfg.clone(),
+            )
+            .unwrap_err();
+            format!("{err:?}")
+        };
+        assert!(forged(r#"{"nope": []}"#).contains("unknown function `nope`"));
+        assert!(forged(r#"{"total": []}"#).contains("printed 0 outputs for `total`"));
+    }
+}
@@ -1,25 +1,23 @@
-use crate::runner::{run, RunOutput};
-use crate::{lang::Language, var_type::VariableType};
+use crate::cache::hash;
+use crate::codegen::{codegen, TemplateData};
+use crate::compare::Comparator;
+use crate::constraints::Constraint;
+use crate::runner::{run, truncate, ExitStatus, RunOutput};
+use crate::stdio::IoConfig;
+use crate::{
+    lang::Language,
+    var_type::{parse_type_table, VariableType},
+};
 use amplitude_common::config::Config;
 use amplitude_common::path;
 
 use anyhow::Context;
 use handlebars::Handlebars;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Serialize, Serializer};
 use serde_json::{json, Value};
 
-use std::collections::HashMap;
-
-#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
-pub struct Field {
-    pub name: String,
-    pub ty: VariableType,
-}
-
-#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
-pub struct DynStruct {
-    pub fields: Vec<Field>,
-}
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Component, Path};
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(deny_unknown_fields)]
@@ -32,28 +30,252 @@ pub struct FunctionConfig {
     pub hidden_cases: u32,
     #[serde(default = "visible_cases_default")]
     pub visible_cases: u32,
+    /// How outputs are checked against the expected output
+    #[serde(default)]
+    pub compare: Comparator,
+    /// How much the function counts towards the exercise's score
+    #[serde(default = "weight_default")]
+    pub weight: f64,
+    /// How much each test case counts towards the function's score, visible
+    /// ones first. Every test case counts the same if it's empty.
+    #[serde(default, skip_serializing)]
+    pub case_weights: Vec<f64>,
+    /// Constraints on each input, to generate test cases without a generator,
+    /// see [`generate_from_constraints`](crate::constraints::generate_from_constraints)
+    #[serde(skip_serializing)]
+    pub constraints: Option<Vec<Constraint>>,
     #[serde(skip_deserializing)]
     pub tests: Vec<TestCase>,
 }
 
+impl FunctionConfig {
+    fn case_weight(&self, i: usize) -> f64 {
+        self.case_weights.get(i).copied().unwrap_or(1.0)
+    }
+
+    /// How each test case went, from whether the one at each index passed
+    fn case_outcomes(&self, passed: impl Fn(usize) -> bool) -> Vec<CaseOutcome> {
+        (0..(self.visible_cases + self.hidden_cases) as usize)
+            .map(|i| CaseOutcome {
+                hidden: i >= self.visible_cases as usize,
+                passed: passed(i),
+                weight: self.case_weight(i),
+            })
+            .collect()
+    }
+
+    /// Make sure the inputs and output of a test case have the right types
+    pub fn check_test(&self, test: &TestCase) -> anyhow::Result<()> {
+        anyhow::ensure!(
+            test.inputs.len() == self.inputs.len(),
+            "Expected {} inputs, found {}",
+            self.inputs.len(),
+            test.inputs.len()
+        );
+        for (i, (ty, value)) in self.inputs.iter().zip(&test.inputs).enumerate() {
+            ty.check_value(value)
+                .with_context(|| format!("In input {i}"))?;
+        }
+        self.output.check_value(&test.output).context("In output")
+    }
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
-#[serde(deny_unknown_fields)]
+#[serde(try_from = "RawExerciseConfig")]
 pub struct ExerciseConfig {
     pub title: String,
-    #[serde(skip_deserializing)]
     pub instructions: String,
     pub functions: HashMap<String, FunctionConfig>,
+    /// Set for exercises where the code is a whole program, instead of
+    /// `functions`
+    pub io: Option<IoConfig>,
+    /// The named types from the `[types]` table, which are declared in every
+    /// harness even if no function uses them
+    #[serde(skip_serializing)]
+    pub types: HashMap<String, VariableType>,
+    /// Files next to the code in every run, by their path in the sandbox.
+    /// Only the visible ones are sent to the editor.
+    #[serde(serialize_with = "serialize_fixtures")]
+    pub fixtures: BTreeMap<String, Fixture>,
+}
+
+/// A file the code can use, like a data file, a helper module or a header.
+/// Declared in the config, with its content in the exercise's `fixtures`
+/// directory.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Fixture {
+    /// Shown read-only in the editor, so it has to be text
+    #[serde(default)]
+    pub visible: bool,
+    #[serde(skip)]
+    pub content: Vec<u8>,
+}
+
+/// The content of the visible fixtures by path
+fn serialize_fixtures<S: Serializer>(
+    fixtures: &BTreeMap<String, Fixture>,
+    s: S,
+) -> Result<S::Ok, S::Error> {
+    s.collect_map(
+        fixtures
+            .iter()
+            .filter(|(_, fixture)| fixture.visible)
+            .map(|(path, fixture)| (path, String::from_utf8_lossy(&fixture.content))),
+    )
+}
+
+/// An [`ExerciseConfig`] as it's written, before the named types in the
+/// functions are resolved
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawExerciseConfig {
+    title: String,
+    #[serde(default)]
+    functions: HashMap<String, FunctionConfig>,
+    io: Option<IoConfig>,
+    #[serde(default)]
+    types: HashMap<String, String>,
+    #[serde(default)]
+    fixtures: BTreeMap<String, Fixture>,
+}
+
+impl TryFrom<RawExerciseConfig> for ExerciseConfig {
+    /// Serde only shows the `Display` of errors, which would leave out
+    /// everything but the outermost context
+    type Error = String;
+
+    fn try_from(raw: RawExerciseConfig) -> Result<Self, Self::Error> {
+        Self::from_raw(raw).map_err(|e| format!("{e:#}"))
+    }
+}
+
+impl ExerciseConfig {
+    fn from_raw(raw: RawExerciseConfig) -> anyhow::Result<Self> {
+        let types = parse_type_table(&raw.types).context("Invalid `[types]`")?;
+        let mut functions = raw.functions;
+        for (name, func) in &mut functions {
+            func.inputs
+                .iter_mut()
+                .chain([&mut func.output])
+                .try_for_each(|ty| ty.resolve(&types))
+                .with_context(|| format!("In function `{name}`"))?;
+        }
+
+        Ok(Self {
+            title: raw.title,
+            instructions: String::new(),
+            functions,
+            io: raw.io,
+            types,
+            fixtures: raw.fixtures,
+        })
+    }
+
+    /// Seed the test case generation of every function from the exercise's
+    /// id and the function's name. Hashed with SHA-256 so seeds stay the same
+    /// across Rust releases.
+    pub fn set_seeds(&mut self, id: &str) {
+        for (func, cfg) in self.functions.iter_mut() {
+            let hash = hash([id.as_bytes(), func.as_bytes()]);
+            cfg.seed = u64::from_str_radix(&hash[..16], 16).unwrap();
+        }
+    }
+
+    /// The files of the fixtures, to run the code with
+    pub fn fixture_files(&self) -> HashMap<String, &[u8]> {
+        self.fixtures
+            .iter()
+            .map(|(path, fixture)| (path.clone(), fixture.content.as_slice()))
+            .collect()
+    }
+
+    /// Make sure the config makes sense, beyond what's checked when it's
+    /// deserialized
+    pub fn check(&self) -> anyhow::Result<()> {
+        for path in self.fixtures.keys() {
+            let path_ok = !path.is_empty()
+                && Path::new(path)
+                    .components()
+                    .all(|c| matches!(c, Component::Normal(_)));
+            anyhow::ensure!(
+                path_ok,
+                "Fixture `{path}` has to be a relative path, without `.` or `..`"
+            );
+        }
+        match &self.io {
+            Some(io) => {
+                anyhow::ensure!(
+                    self.functions.is_empty(),
+                    "An exercise can't have both `functions` and `io`"
+                );
+                anyhow::ensure!(!io.tests.is_empty(), "`io` has no test cases");
+                for (i, test) in io.tests.iter().enumerate() {
+                    check_weight(test.weight)
+                        .with_context(|| format!("Invalid weight for `io` test case {i}"))?;
+                }
+            }
+            None => anyhow::ensure!(
+                !self.functions.is_empty(),
+                "An exercise needs either `functions` or `io`"
+            ),
+        }
+        for (func, cfg) in &self.functions {
+            cfg.compare
+                .check(&cfg.output)
+                .with_context(|| format!("Invalid comparator for `{func}`"))?;
+            check_weight(cfg.weight).with_context(|| format!("Invalid weight for `{func}`"))?;
+            if !cfg.case_weights.is_empty() {
+                let cases = cfg.visible_cases + cfg.hidden_cases;
+                anyhow::ensure!(
+                    cfg.case_weights.len() == cases as usize,
+                    "`{func}` has {cases} test cases, but {} case weights",
+                    cfg.case_weights.len()
+                );
+            }
+            for (i, weight) in cfg.case_weights.iter().enumerate() {
+                check_weight(*weight)
+                    .with_context(|| format!("Invalid weight for test case {i} of `{func}`"))?;
+            }
+            if let Some(constraints) = &cfg.constraints {
+                anyhow::ensure!(
+                    constraints.len() == cfg.inputs.len(),
+                    "`{func}` has {} inputs, but {} constraints",
+                    cfg.inputs.len(),
+                    constraints.len()
+                );
+                for (i, (constraint, ty)) in constraints.iter().zip(&cfg.inputs).enumerate() {
+                    constraint
+                        .check(ty)
+                        .with_context(|| format!("Invalid constraint for input {i} of `{func}`"))?;
+                }
+            }
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug, Clone, Serialize)]
 pub struct Exercise {
     pub config: ExerciseConfig,
     pub lang_info: HashMap<Language, LanguageInfo>,
+    #[serde(skip_serializing)]
+    pub checker: Option<Checker>,
+}
+
+/// A program that decides if outputs are correct, for functions with more
+/// than one correct output. Used instead of comparing outputs when an exercise
+/// has one.
+#[derive(Debug, Clone)]
+pub struct Checker {
+    pub lang: Language,
+    pub code: String,
 }
 
 #[derive(Debug, Clone, Serialize)]
 pub struct LanguageInfo {
     pub code: String,
+    /// The harness that calls the functions, empty for `io` exercises
     #[serde(skip_serializing)]
     pub runner: String,
 }
@@ -62,28 +284,189 @@ pub struct LanguageInfo {
 #[serde(tag = "type")]
 pub enum TestResult {
     #[serde(rename = "correct")]
-    Correct { stdout: String },
+    Correct {
+        stdout: String,
+        /// Set by the checker, if there is one
+        #[serde(skip_serializing_if = "Option::is_none")]
+        message: Option<String>,
+    },
     #[serde(rename = "incorrect")]
-    Incorrect { stdout: String, output: Value },
+    Incorrect {
+        stdout: String,
+        output: Value,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        message: Option<String>,
+    },
     #[serde(rename = "error")]
     Error { traceback: String, stdout: String },
+    #[serde(rename = "time_limit_exceeded")]
+    TimeLimitExceeded { runtime_ms: u64 },
+    #[serde(rename = "memory_limit_exceeded")]
+    MemoryLimitExceeded,
+    #[serde(rename = "output_limit_exceeded")]
+    OutputLimitExceeded,
+    #[serde(rename = "runtime_error")]
+    RuntimeError { exit_code: i32, stderr: String },
 }
 
-#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+impl TestResult {
+    /// Cut everything the code printed down to `max` bytes
+    fn truncate(&mut self, max: usize) {
+        match self {
+            TestResult::Correct { stdout, message }
+            | TestResult::Incorrect {
+                stdout, message, ..
+            } => {
+                truncate(stdout, max);
+                if let Some(message) = message {
+                    truncate(message, max);
+                }
+            }
+            TestResult::Error { traceback, stdout } => {
+                truncate(traceback, max);
+                truncate(stdout, max);
+            }
+            TestResult::RuntimeError { stderr, .. } => truncate(stderr, max),
+            TestResult::TimeLimitExceeded { .. }
+            | TestResult::MemoryLimitExceeded
+            | TestResult::OutputLimitExceeded => {}
+        }
+    }
+
+    /// The verdict for a run that was killed or crashed before it could
+    /// report any results, or `None` if it exited normally.
+    pub fn from_run_output(output: &RunOutput) -> Option<Self> {
+        Some(match output.status {
+            ExitStatus::Success => return None,
+            ExitStatus::Timeout => TestResult::TimeLimitExceeded {
+                runtime_ms: output.runtime.as_millis() as u64,
+            },
+            ExitStatus::OutOfMemory => TestResult::MemoryLimitExceeded,
+            ExitStatus::OutputLimit => TestResult::OutputLimitExceeded,
+            ExitStatus::Failure | ExitStatus::Signal(_) => TestResult::RuntimeError {
+                exit_code: output.exit_code,
+                stderr: output.stderr.clone(),
+            },
+        })
+    }
+}
+
+/// The key of the results of an `io` exercise, in place of a function name
+pub const IO_RESULTS: &str = "io";
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
 pub struct TestResults {
+    /// The results of the visible test cases
     pub results: Vec<TestResult>,
+    pub hidden: HiddenResults,
+    pub passed: bool,
+    /// The weighted fraction of the test cases that passed, from 0 to 1
+    pub score: f64,
+    /// How much `score` counts towards the exercise's, see [`total_score`]
+    pub weight: f64,
+}
+
+/// How many hidden test cases passed, without saying which
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+pub struct HiddenResults {
+    pub passed: u32,
+    pub total: u32,
+}
+
+/// How a single test case went, to score it
+pub(crate) struct CaseOutcome {
     pub hidden: bool,
     pub passed: bool,
+    pub weight: f64,
+}
+
+impl TestResults {
+    /// `results` are the results of the visible test cases, and `cases` how
+    /// every test case went
+    pub(crate) fn new(results: Vec<TestResult>, cases: &[CaseOutcome], weight: f64) -> Self {
+        let hidden = cases.iter().filter(|x| x.hidden);
+        let hidden = HiddenResults {
+            passed: hidden.clone().filter(|x| x.passed).count() as u32,
+            total: hidden.count() as u32,
+        };
+        let passed = cases.iter().all(|x| x.passed);
+
+        let total = cases.iter().map(|x| x.weight).sum::<f64>();
+        let score = match total > 0.0 {
+            true => {
+                cases
+                    .iter()
+                    .filter(|x| x.passed)
+                    .map(|x| x.weight)
+                    .sum::<f64>()
+                    / total
+            }
+            // nothing counts, so it's all or nothing
+            false => passed as u8 as f64,
+        };
+
+        Self {
+            results,
+            hidden,
+            passed,
+            score,
+            weight,
+        }
+    }
+}
+
+/// The score of a whole exercise from 0 to 1, the weighted average of the
+/// scores of its functions. Nothing to score is a score of 0.
+pub fn total_score(results: &HashMap<String, TestResults>) -> f64 {
+    if results.is_empty() {
+        return 0.0;
+    }
+    let total = results.values().map(|x| x.weight).sum::<f64>();
+    if total <= 0.0 {
+        return results.values().all(|x| x.passed) as u8 as f64;
+    }
+    results.values().map(|x| x.score * x.weight).sum::<f64>() / total
+}
+
+/// Weights can be anything but negative
+pub(crate) fn check_weight(weight: f64) -> anyhow::Result<()> {
+    anyhow::ensure!(
+        weight.is_finite() && weight >= 0.0,
+        "Weights can't be negative, found {weight}"
+    );
+    Ok(())
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type")]
+pub(crate) enum TestOutput {
+    #[serde(rename = "answer")]
+    Answer {
+        value: serde_json::Value,
+        stdout: String,
+    },
+    #[serde(rename = "exception")]
+    Exception { traceback: String, stdout: String },
 }
 
+/// Whether an output is correct, and why
+type Verdict = (bool, Option<String>);
+
 impl Exercise {
-    pub fn new(config: ExerciseConfig, language_info: HashMap<Language, LanguageInfo>) -> Self {
+    pub fn new(
+        config: ExerciseConfig,
+        language_info: HashMap<Language, LanguageInfo>,
+        checker: Option<Checker>,
+    ) -> Self {
         Self {
             config,
             lang_info: language_info,
+            checker,
         }
     }
 
+    /// Run `content` against every test case, with the output of each one cut
+    /// down to `[docker.output] test`
     pub fn run_tests(
         &self,
         lang: &Language,
@@ -91,87 +474,265 @@ impl Exercise {
         id: &str,
         cfg: &Config,
     ) -> anyhow::Result<HashMap<String, TestResults>> {
-        #[derive(Debug, Deserialize)]
-        #[serde(tag = "type")]
-        enum TestOutput {
-            #[serde(rename = "answer")]
-            Answer {
-                value: serde_json::Value,
-                stdout: String,
-            },
-            #[serde(rename = "exception")]
-            Exception { traceback: String, stdout: String },
+        let mut results = self.test_results(lang, content, id, cfg)?;
+        for result in results.values_mut().flat_map(|x| &mut x.results) {
+            result.truncate(cfg.docker.output.test);
         }
+        Ok(results)
+    }
+
+    fn test_results(
+        &self,
+        lang: &Language,
+        content: &str,
+        id: &str,
+        cfg: &Config,
+    ) -> anyhow::Result<HashMap<String, TestResults>> {
         let runner = &self
             .lang_info
             .get(lang)
             .with_context(|| format!("Language `{}` not found for this question", lang.image()))?
             .runner;
-        let RunOutput { stdout, stderr, .. } = run(
+        if let Some(io) = &self.config.io {
+            let results = io.run_tests(lang, content, &self.config.fixture_files(), cfg)?;
+            return Ok(HashMap::from_iter([(IO_RESULTS.to_string(), results)]));
+        }
+        let output = run(
             cfg.docker.language_config.get(lang.image()).unwrap(),
             &cfg.docker,
             runner,
-            HashMap::from_iter([(id.to_string() + ".py", content.as_bytes())]),
+            with_fixtures(&self.config, [(lang.code_path(id), content.as_bytes())]),
             "",
         )
         .context("While running generator")?;
 
+        // the whole run died, so every test gets the same verdict
+        if let Some(verdict) = TestResult::from_run_output(&output) {
+            return Ok(self
+                .config
+                .functions
+                .iter()
+                .map(|(func, fn_config)| {
+                    let results = vec![verdict.clone(); fn_config.visible_cases as usize];
+                    let cases = fn_config.case_outcomes(|_| false);
+                    (
+                        func.clone(),
+                        TestResults::new(results, &cases, fn_config.weight),
+                    )
+                })
+                .collect());
+        }
+
+        let RunOutput { stdout, stderr, .. } = output;
         let test_cases: HashMap<String, Vec<TestOutput>> = serde_json::from_str(&stdout)
             .with_context(|| {
                 format!("While parsing test cases\nstdout:\n{stdout}\n\nstderr:\n{stderr}")
             })?;
-        let mut results = HashMap::new();
 
-        for (func, suite) in test_cases {
-            let fn_config = &self.config.functions[&func];
-            let tests = &fn_config.tests;
-            let (visible, hidden) = suite.as_slice().split_at(fn_config.visible_cases as usize);
+        let checked = match &self.checker {
+            Some(checker) => Some(self.check(checker, &test_cases, cfg)?),
+            None => None,
+        };
+        let verdict =
+            |func: &str, fn_config: &FunctionConfig, i: usize, value: &Value| -> Verdict {
+                // only a submission printing its own results gets more of them
+                // than there are test cases
+                let Some(test) = fn_config.tests.get(i) else {
+                    return (false, Some(format!("There is no test case {i}")));
+                };
+                match &checked {
+                    Some(checked) => checked
+                        .get(&(func.to_string(), i))
+                        .cloned()
+                        // it wasn't checked because it has the wrong type
+                        .unwrap_or_else(|| {
+                            let msg = format!("Expected a value of type `{}`", fn_config.output);
+                            (false, Some(msg))
+                        }),
+                    None => (
+                        fn_config
+                            .compare
+                            .matches(&fn_config.output, &test.output, value),
+                        None,
+                    ),
+                }
+            };
 
-            let mut visible_passed = true;
-            let visible = visible
-                .iter()
-                .enumerate()
-                .map(|(i, t)| match t {
+        let mut results = HashMap::new();
+        for (func, suite) in &test_cases {
+            let fn_config = self.function(func)?;
+
+            let mut visible = Vec::new();
+            let mut passed = Vec::new();
+            for (i, t) in suite.iter().enumerate() {
+                let result = match t {
                     TestOutput::Answer { value, stdout } => {
                         let stdout = stdout.to_string();
-                        // dbg!(value, &tests[i].output);
-                        match value == &tests[i].output {
-                            true => TestResult::Correct { stdout },
-                            false => {
-                                visible_passed = false;
-                                TestResult::Incorrect {
-                                    stdout,
-                                    output: value.clone(),
-                                }
-                            }
+                        match verdict(func, fn_config, i, value) {
+                            (true, message) => TestResult::Correct { stdout, message },
+                            (false, message) => TestResult::Incorrect {
+                                stdout,
+                                output: value.clone(),
+                                message,
+                            },
                         }
                     }
-                    TestOutput::Exception { traceback, stdout } => {
-                        let traceback = traceback.to_string();
-                        let stdout = stdout.to_string();
-                        visible_passed = false;
-                        TestResult::Error { traceback, stdout }
-                    }
-                })
-                .collect();
-            let hidden = hidden.iter().enumerate().all(|(i, t)| match t {
-                TestOutput::Answer { value, .. } => {
-                    &tests[i + fn_config.visible_cases as usize].output == value
+                    TestOutput::Exception { traceback, stdout } => TestResult::Error {
+                        traceback: traceback.to_string(),
+                        stdout: stdout.to_string(),
+                    },
+                };
+                passed.push(matches!(result, TestResult::Correct { .. }));
+                if i < fn_config.visible_cases as usize {
+                    visible.push(result);
                 }
-                TestOutput::Exception { .. } => false,
-            });
+            }
+
+            // test cases the harness didn't get to didn't pass
+            let cases = fn_config.case_outcomes(|i| passed.get(i).copied().unwrap_or(false));
             results.insert(
-                func,
-                TestResults {
-                    results: visible,
-                    hidden,
-                    passed: visible_passed && hidden,
-                },
+                func.clone(),
+                TestResults::new(visible, &cases, fn_config.weight),
+            );
+        }
+
+        // the code can print results and exit before the harness does, so
+        // functions it didn't print anything for fail
+        for (func, fn_config) in &self.config.functions {
+            if test_cases.contains_key(func) {
+                continue;
+            }
+            let error = TestResult::Error {
+                traceback: format!("There are no results for `{func}`"),
+                stdout: String::new(),
+            };
+            let visible = vec![error; fn_config.visible_cases as usize];
+            let cases = fn_config.case_outcomes(|_| false);
+            results.insert(
+                func.clone(),
+                TestResults::new(visible, &cases, fn_config.weight),
             );
         }
 
         Ok(results)
     }
+
+    /// The config of a function from the output of a run. Student code can
+    /// print anything before the harness does, so it might not exist.
+    fn function(&self, func: &str) -> anyhow::Result<&FunctionConfig> {
+        self.config.functions.get(func).with_context(|| {
+            format!("Got results for `{func}`, which isn't a function of this exercise")
+        })
+    }
+
+    /// Make sure a reference solution passes every test case, to catch broken
+    /// test cases and harness bugs before anyone else runs into them. The
+    /// error lists what failed.
+    pub fn verify_solution(
+        &self,
+        lang: &Language,
+        content: &str,
+        id: &str,
+        cfg: &Config,
+    ) -> anyhow::Result<()> {
+        anyhow::ensure!(
+            self.lang_info.contains_key(lang),
+            "The exercise isn't offered in {lang:?}"
+        );
+        let results = self
+            .run_tests(lang, content, id, cfg)
+            .context("While running reference solution")?;
+
+        let mut failures = Vec::new();
+        let mut names = results.keys().collect::<Vec<_>>();
+        names.sort();
+        for name in names {
+            let suite = &results[name];
+            for (i, result) in suite.results.iter().enumerate() {
+                if !matches!(result, TestResult::Correct { .. }) {
+                    failures.push(format!("`{name}` failed test case {i}: {result:?}"));
+                }
+            }
+            let HiddenResults { passed, total } = suite.hidden;
+            if passed < total {
+                failures.push(format!(
+                    "`{name}` failed {} of {total} hidden test cases",
+                    total - passed
+                ));
+            }
+        }
+
+        anyhow::ensure!(
+            failures.is_empty(),
+            "The reference solution doesn't pass:\n{}",
+            failures.join("\n")
+        );
+        Ok(())
+    }
+
+    /// Run the checker on every output of the right type, getting a verdict
+    /// for each one by function name and test index
+    fn check(
+        &self,
+        checker: &Checker,
+        test_cases: &HashMap<String, Vec<TestOutput>>,
+        cfg: &Config,
+    ) -> anyhow::Result<HashMap<(String, usize), Verdict>> {
+        #[derive(Debug, Deserialize)]
+        struct CheckOutput {
+            index: usize,
+            passed: bool,
+            message: String,
+        }
+
+        let gen = codegen(&checker.lang);
+        let mut outputs = HashMap::new();
+        for (func, suite) in test_cases {
+            let fn_config = self.function(func)?;
+            let values = suite
+                .iter()
+                .take(fn_config.tests.len())
+                .enumerate()
+                .filter_map(|(i, t)| match t {
+                    TestOutput::Answer { value, .. } => Some((i, value.clone())),
+                    TestOutput::Exception { .. } => None,
+                })
+                // outputs of the wrong type can't be passed to the checker
+                .filter(|(_, value)| gen.literal(&fn_config.output, value).is_ok())
+                .collect::<Vec<_>>();
+            outputs.insert(func.clone(), values);
+        }
+        if outputs.values().all(|x| x.is_empty()) {
+            return Ok(HashMap::new());
+        }
+
+        let RunOutput { stdout, stderr, .. } = run(
+            cfg.docker
+                .language_config
+                .get(checker.lang.image())
+                .unwrap(),
+            &cfg.docker,
+            &checker_template(&checker.lang, &checker.code, &self.config, &outputs)
+                .context("While generating checker template")?,
+            self.config.fixture_files(),
+            "",
+        )
+        .context("While running checker")?;
+
+        let checks: HashMap<String, Vec<CheckOutput>> = serde_json::from_str(&stdout)
+            .with_context(|| {
+                format!("While parsing checker output\nstdout:\n{stdout}\n\nstderr:\n{stderr}")
+            })?;
+        Ok(checks
+            .into_iter()
+            .flat_map(|(func, checks)| {
+                checks.into_iter().map(move |check| {
+                    let message = Some(check.message).filter(|x| !x.is_empty());
+                    ((func.clone(), check.index), (check.passed, message))
+                })
+            })
+            .collect())
+    }
 }
 
 // impl Into<ExcerciseSerialize> for Exercise {
@@ -189,24 +750,26 @@ pub fn runner_template(lang: &Language, cfg: &ExerciseConfig, id: &str) -> anyho
     handlebars
         .register_template_file(
             "runner",
-            &path::LANGUAGES.join(lang.image()).join("runner.hbs"),
+            path::LANGUAGES.join(lang.image()).join("runner.hbs"),
         )
         .context("While registering template file")?;
     handlebars.register_escape_fn(handlebars::no_escape);
     handlebars.set_strict_mode(true);
+    let data = TemplateData::new(lang, cfg).context("While generating code for test cases")?;
     let out = handlebars
         .render(
             "runner",
             &json!({
                 "code_file": id,
-                "functions": &cfg.functions,
+                "functions": data.functions,
+                "type_defs": data.type_defs,
             }),
         )
         .context("While rendering template file")?;
     Ok(out)
 }
 
-#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct TestCase {
     pub inputs: Vec<serde_json::Value>,
     pub output: serde_json::Value,
@@ -214,6 +777,10 @@ pub struct TestCase {
     pub hidden: bool,
 }
 
+const fn weight_default() -> f64 {
+    1.0
+}
+
 const fn hidden_cases_default() -> u32 {
     5
 }
@@ -231,16 +798,53 @@ pub fn generator_template(
     handlebars
         .register_template_file(
             "generator",
-            &path::LANGUAGES.join(lang.image()).join("generator.hbs"),
+            path::LANGUAGES.join(lang.image()).join("generator.hbs"),
         )
         .context("While registerring template file")?;
     handlebars.register_escape_fn(handlebars::no_escape);
+    handlebars.set_strict_mode(true);
+    let data = TemplateData::new(lang, exercise_cfg).context("While generating code for types")?;
     let out = handlebars
         .render(
             "generator",
             &json!({
                 "content": content,
-                "functions": &exercise_cfg.functions,
+                "functions": data.functions,
+                "type_defs": data.type_defs,
+            }),
+        )
+        .context("While rendering template")?;
+    Ok(out)
+}
+
+/// Render a checker, that checks `outputs` (`(test index, output)` for each
+/// function)
+pub fn checker_template(
+    lang: &Language,
+    content: &str,
+    exercise_cfg: &ExerciseConfig,
+    outputs: &HashMap<String, Vec<(usize, Value)>>,
+) -> anyhow::Result<String> {
+    let mut handlebars = Handlebars::new();
+    handlebars
+        .register_template_file(
+            "checker",
+            path::LANGUAGES.join(lang.image()).join("checker.hbs"),
+        )
+        .context("While registering template file")?;
+    handlebars.register_escape_fn(handlebars::no_escape);
+    handlebars.set_strict_mode(true);
+    let mut data =
+        TemplateData::new(lang, exercise_cfg).context("While generating code for types")?;
+    data.add_checks(lang, exercise_cfg, outputs)
+        .context("While generating code for outputs")?;
+    let out = handlebars
+        .render(
+            "checker",
+            &json!({
+                "content": content,
+                "functions": data.functions,
+                "type_defs": data.type_defs,
             }),
         )
         .context("While rendering template")?;
@@ -253,20 +857,59 @@ pub fn generate(
     content: &str,
     exercise_cfg: &mut ExerciseConfig,
 ) -> anyhow::Result<()> {
-    let RunOutput { stdout, stderr, .. } = run(
+    let program =
+        generator_template(lang, content, exercise_cfg).context("While generating template")?;
+    let test_cases = run_generator(lang, cfg, &program, exercise_cfg)?;
+    set_test_cases(exercise_cfg, test_cases)
+}
+
+/// Run a rendered generator and get the test cases it generated for each
+/// function
+pub(crate) fn run_generator(
+    lang: &Language,
+    cfg: &Config,
+    program: &str,
+    exercise_cfg: &ExerciseConfig,
+) -> anyhow::Result<HashMap<String, Vec<TestCase>>> {
+    let RunOutput {
+        stdout,
+        stderr,
+        status,
+        ..
+    } = run(
         cfg.docker.language_config.get(lang.image()).unwrap(),
         &cfg.docker,
-        &generator_template(lang, content, exercise_cfg).expect("While generating template"),
-        HashMap::new(),
+        program,
+        exercise_cfg.fixture_files(),
         "",
     )
     .context("While running generator")?;
+    anyhow::ensure!(
+        status != ExitStatus::OutputLimit,
+        "The generator printed more than the output limit of {} bytes",
+        cfg.docker.output.run
+    );
 
-    let mut test_cases: HashMap<String, Vec<TestCase>> = serde_json::from_str(&stdout)
-        .with_context(|| {
-            format!("While parsing test cases\nstdout:\n{stdout}\n\nstderr:\n{stderr}")
-        })?;
+    serde_json::from_str(&stdout).with_context(|| {
+        format!("While parsing test cases\nstdout:\n{stdout}\n\nstderr:\n{stderr}")
+    })
+}
 
+/// The fixtures of `exercise_cfg` and some other files, to run code with
+pub(crate) fn with_fixtures<'a>(
+    exercise_cfg: &'a ExerciseConfig,
+    files: impl IntoIterator<Item = (String, &'a [u8])>,
+) -> HashMap<String, &'a [u8]> {
+    let mut out = exercise_cfg.fixture_files();
+    out.extend(files);
+    out
+}
+
+/// Give each function its generated test cases
+pub(crate) fn set_test_cases(
+    exercise_cfg: &mut ExerciseConfig,
+    mut test_cases: HashMap<String, Vec<TestCase>>,
+) -> anyhow::Result<()> {
     for (func, mut tests) in test_cases.drain() {
         let cfg = &mut exercise_cfg
             .functions
@@ -277,7 +920,12 @@ pub fn generate(
             "Test cases for function `{}` < cfg.hidden_cases + cfg.visible_cases",
             func
         );
-        for test in tests.iter_mut().skip(cfg.hidden_cases as usize) {
+        for (i, test) in tests.iter().enumerate() {
+            cfg.check_test(test)
+                .with_context(|| format!("Invalid test case {i} for `{func}`"))?;
+        }
+        // generators write the visible cases first
+        for test in tests.iter_mut().skip(cfg.visible_cases as usize) {
             test.hidden = true
         }
         cfg.tests = tests;
@@ -295,14 +943,37 @@ mod test {
     #[test]
     fn test_simple_langs() {
         test_simple(&Language::Python, "def test(x):\n    return x - 1\n").unwrap();
+        test_simple(
+            &Language::JavaScript,
+            "function test(x) {\n    return x - 1;\n}\n",
+        )
+        .unwrap();
+        test_simple(
+            &Language::C,
+            "long long test(long long x) {\n    return x - 1;\n}\n",
+        )
+        .unwrap();
+        test_simple(
+            &Language::Cpp,
+            "long long test(long long x) {\n    return x - 1;\n}\n",
+        )
+        .unwrap();
+        test_simple(&Language::Rust, "fn test(x: i64) -> i64 {\n    x - 1\n}\n").unwrap();
+        test_simple(
+            &Language::Java,
+            "public class Solution {\n    public static long test(long x) {\n        return x - 1;\n    }\n}\n",
+        )
+        .unwrap();
     }
 
-    fn test_simple(lang: &Language, code: &str) -> anyhow::Result<()> {
-        let cfg = config_and_set_path().unwrap();
-
-        let config = ExerciseConfig {
+    /// `test(x) = x - 1`, with two visible and two hidden test cases
+    fn simple_config() -> ExerciseConfig {
+        ExerciseConfig {
             title: "test".to_string(),
             instructions: "test".to_string(),
+            io: None,
+            types: HashMap::new(),
+            fixtures: BTreeMap::new(),
             functions: HashMap::from_iter([(
                 "test".to_string(),
                 FunctionConfig {
@@ -311,6 +982,10 @@ mod test {
                     seed: 0,
                     hidden_cases: 2,
                     visible_cases: 2,
+                    compare: Comparator::Exact,
+                    weight: 1.0,
+                    case_weights: Vec::new(),
+                    constraints: None,
                     tests: vec![
                         TestCase {
                             inputs: vec![json!(1)],
@@ -335,9 +1010,13 @@ mod test {
                     ],
                 },
             )]),
-        };
+        }
+    }
 
-        let exercise = Exercise {
+    /// An exercise offered in `lang` without a checker, with the runner
+    /// calling code saved as `test`
+    fn exercise(lang: &Language, config: ExerciseConfig) -> Exercise {
+        Exercise {
             lang_info: HashMap::from_iter([(
                 *lang,
                 LanguageInfo {
@@ -346,26 +1025,475 @@ mod test {
                 },
             )]),
             config,
-        };
+            checker: None,
+        }
+    }
+
+    fn test_simple(lang: &Language, code: &str) -> anyhow::Result<()> {
+        let cfg = config_and_set_path().unwrap();
+        let config = simple_config();
+
+        let exercise = exercise(lang, config);
         let result = exercise.run_tests(lang, code, "test", &cfg)?;
         anyhow::ensure!(
             result["test"]
                 == TestResults {
                     results: vec![
                         TestResult::Correct {
-                            stdout: "".to_string()
+                            stdout: "".to_string(),
+                            message: None,
                         },
                         TestResult::Correct {
-                            stdout: "".to_string()
+                            stdout: "".to_string(),
+                            message: None,
                         }
                     ],
-                    hidden: true,
-                    passed: true
+                    hidden: HiddenResults {
+                        passed: 2,
+                        total: 2
+                    },
+                    passed: true,
+                    score: 1.0,
+                    weight: 1.0,
                 }
         );
         Ok(())
     }
 
+    #[test]
+    fn test_verify_solution() {
+        let cfg = config_and_set_path().unwrap();
+        let config = simple_config();
+        let lang = Language::Python;
+        let exercise = exercise(&lang, config);
+
+        let verify = |code| exercise.verify_solution(&lang, code, "test", &cfg);
+        verify("def test(x):\n    return x - 1\n").unwrap();
+        assert_eq!(
+            verify("def test(x):\n    return 2 if x == 3 else 0\n")
+                .unwrap_err()
+                .to_string(),
+            "The reference solution doesn't pass:\n`test` failed 2 of 2 hidden test cases"
+        );
+        assert!(verify("def test(x):\n    return x\n")
+            .unwrap_err()
+            .to_string()
+            .contains("`test` failed test case 0: Incorrect"));
+        assert_eq!(
+            exercise
+                .verify_solution(&Language::Rust, "", "test", &cfg)
+                .unwrap_err()
+                .to_string(),
+            "The exercise isn't offered in Rust"
+        );
+    }
+
+    #[test]
+    fn test_forged_results() {
+        let cfg = config_and_set_path().unwrap();
+        let mut config = simple_config();
+        let other = config.functions["test"].clone();
+        config.functions.insert("other".to_string(), other);
+        let lang = Language::Python;
+        let exercise = exercise(&lang, config);
+
+        // printed before the harness runs
+        let forge = |results: &str| {
+            format!(
+                "import sys\nprint('{results}')\nsys.exit(0)\n\ndef test(x):\n    return x - 1\n"
+            )
+        };
+        let answer = r#"{"type": "answer", "value": 0, "stdout": ""}"#;
+
+        let extra = forge(&format!(r#"{{"test": [{}]}}"#, [answer; 6].join(", ")));
+        let result = exercise.run_tests(&lang, &extra, "test", &cfg).unwrap();
+        assert_eq!(result["test"].results.len(), 2);
+        assert_eq!(
+            result["test"].hidden,
+            HiddenResults {
+                passed: 0,
+                total: 2
+            }
+        );
+        assert!(!result["test"].passed);
+
+        let unknown = forge(&format!(r#"{{"nope": [{answer}]}}"#));
+        assert!(exercise
+            .run_tests(&lang, &unknown, "test", &cfg)
+            .unwrap_err()
+            .to_string()
+            .contains("`nope`"));
+
+        // functions without results fail, rather than not counting
+        let empty = exercise
+            .run_tests(&lang, &forge("{}"), "test", &cfg)
+            .unwrap();
+        assert_eq!(empty.len(), 2);
+        assert!(empty.values().all(|x| !x.passed && x.score == 0.0));
+        assert_eq!(total_score(&empty), 0.0);
+
+        let answers = exercise.config.functions["test"]
+            .tests
+            .iter()
+            .map(|test| {
+                format!(
+                    r#"{{"type": "answer", "value": {}, "stdout": ""}}"#,
+                    test.output
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        let partial = forge(&format!(r#"{{"test": [{answers}]}}"#));
+        let result = exercise.run_tests(&lang, &partial, "test", &cfg).unwrap();
+        assert!(result["test"].passed);
+        assert!(!result["other"].passed);
+        assert_eq!(result["other"].score, 0.0);
+        assert_eq!(total_score(&result), 0.5);
+    }
+
+    #[test]
+    fn test_output_limits() {
+        let mut cfg = config_and_set_path().unwrap();
+        cfg.docker.output.test = 10;
+        let config = simple_config();
+        let lang = Language::Python;
+        let exercise = exercise(&lang, config);
+
+        let code = "def test(x):\n    print('a' * 100)\n    return x - 1\n";
+        let result = exercise.run_tests(&lang, code, "test", &cfg).unwrap();
+        assert_eq!(
+            result["test"].results[0],
+            TestResult::Correct {
+                stdout: "aaaaaaaaaa\n[... 91 more bytes]".to_string(),
+                message: None,
+            }
+        );
+
+        cfg.docker.output.run = 1000;
+        let code = "def test(x):\n    print('a' * 1000)\n    return x - 1\n";
+        let result = exercise.run_tests(&lang, code, "test", &cfg).unwrap();
+        assert_eq!(result["test"].results[0], TestResult::OutputLimitExceeded);
+        assert!(!result["test"].passed);
+    }
+
+    #[test]
+    fn test_fixtures() {
+        let cfg = config_and_set_path().unwrap();
+        let mut config = simple_config();
+        config.fixtures = BTreeMap::from([
+            (
+                "helpers.py".to_string(),
+                Fixture {
+                    visible: true,
+                    content: b"def dec(x):\n    return x - 1\n".to_vec(),
+                },
+            ),
+            (
+                "data/secret.txt".to_string(),
+                Fixture {
+                    visible: false,
+                    content: b"secret".to_vec(),
+                },
+            ),
+        ]);
+        config.check().unwrap();
+        assert_eq!(
+            serde_json::to_value(&config).unwrap()["fixtures"],
+            json!({ "helpers.py": "def dec(x):\n    return x - 1\n" })
+        );
+
+        let lang = Language::Python;
+        let exercise = exercise(&lang, config);
+        let code = "from helpers import dec\n\ndef test(x):\n    \
+                    assert open('data/secret.txt').read() == 'secret'\n    return dec(x)\n";
+        exercise.verify_solution(&lang, code, "test", &cfg).unwrap();
+
+        let mut config = simple_config();
+        config
+            .fixtures
+            .insert("../x".to_string(), Fixture::default());
+        assert!(config.check().is_err());
+    }
+
+    /// Optionals, maps and chars, going both ways through every language
+    #[test]
+    fn test_types_langs() {
+        let code = [
+            (
+                Language::Python,
+                "def lookup(m, k):\n    return (m.get(k) if k is not None else None, m)\n",
+            ),
+            (
+                Language::JavaScript,
+                "function lookup(m, k) {\n    return [k !== null && m.has(k) ? m.get(k) : null, m];\n}\n",
+            ),
+            (
+                Language::C,
+                "Tuple2OptionOfIntMapOfCharToInt lookup(MapOfCharToInt m, OptionOfChar k) {\n    \
+                 OptionOfInt out = {false};\n    \
+                 for (size_t i = 0; k.has_value && i < m.len; i++) {\n        \
+                 if (m.keys[i] == k.value) out = (OptionOfInt){true, m.values[i]};\n    \
+                 }\n    \
+                 return (Tuple2OptionOfIntMapOfCharToInt){out, m};\n}\n",
+            ),
+            (
+                Language::Cpp,
+                "std::tuple<std::optional<long long>, std::map<char, long long>> \
+                 lookup(std::map<char, long long> m, std::optional<char> k) {\n    \
+                 std::optional<long long> out;\n    \
+                 if (k && m.count(*k)) out = m[*k];\n    \
+                 return {out, m};\n}\n",
+            ),
+            (
+                Language::Rust,
+                "fn lookup(\n    m: std::collections::HashMap<char, i64>,\n    k: Option<char>,\n\
+                 ) -> (Option<i64>, std::collections::HashMap<char, i64>) {\n    \
+                 (k.and_then(|k| m.get(&k).copied()), m)\n}\n",
+            ),
+            (
+                Language::Java,
+                "import java.util.*;\n\npublic class Solution {\n    \
+                 public static Tuple2OptionOfIntMapOfCharToInt lookup(HashMap<Character, Long> m, Character k) {\n        \
+                 return new Tuple2OptionOfIntMapOfCharToInt(k == null ? null : m.get(k), m);\n    \
+                 }\n}\n",
+            ),
+        ];
+
+        let cfg = config_and_set_path().unwrap();
+        let mut config: ExerciseConfig = toml::from_str(
+            r#"
+            title = "test"
+            [functions.lookup]
+            inputs = ["map<char, int>", "char?"]
+            output = "(int?, map<char, int>)"
+            visible_cases = 3
+            hidden_cases = 0
+            "#,
+        )
+        .unwrap();
+        let func = config.functions.get_mut("lookup").unwrap();
+        func.tests = [
+            (json!([["a", 1], ["b", 2]]), json!("b"), json!(2)),
+            (json!([]), json!(null), json!(null)),
+            (json!([["'", 3]]), json!("x"), json!(null)),
+        ]
+        .into_iter()
+        .map(|(m, k, out)| TestCase {
+            output: json!([out, m]),
+            inputs: vec![m, k],
+            hidden: false,
+        })
+        .collect();
+        for test in &func.tests {
+            func.check_test(test).unwrap();
+        }
+
+        for (lang, code) in code {
+            let exercise = exercise(&lang, config.clone());
+            let result = exercise.run_tests(&lang, code, "test", &cfg).unwrap();
+            assert!(result["lookup"].passed, "{lang:?}: {:?}", result["lookup"]);
+        }
+    }
+
+    #[test]
+    fn test_named_types_langs() {
+        let code = [
+            (
+                Language::Python,
+                "def total(segs):\n    \
+                 return {\n        \
+                 'y': sum(s['a']['y'] + s['b']['y'] for s in segs),\n        \
+                 'x': sum(s['a']['x'] + s['b']['x'] for s in segs),\n    \
+                 }\n",
+            ),
+            (
+                Language::JavaScript,
+                "function total(segs) {\n    \
+                 const p = {y: 0, x: 0};\n    \
+                 for (const s of segs) {\n        \
+                 p.y += s.a.y + s.b.y;\n        \
+                 p.x += s.a.x + s.b.x;\n    \
+                 }\n    \
+                 return p;\n}\n",
+            ),
+            (
+                Language::C,
+                "Point total(ArrayOfSegment segs) {\n    \
+                 Point p = {0, 0};\n    \
+                 for (size_t i = 0; i < segs.len; i++) {\n        \
+                 p.y += segs.data[i].a.y + segs.data[i].b.y;\n        \
+                 p.x += segs.data[i].a.x + segs.data[i].b.x;\n    \
+                 }\n    \
+                 return p;\n}\n",
+            ),
+            (
+                Language::Cpp,
+                "Point total(std::vector<Segment> segs) {\n    \
+                 Point p{0, 0};\n    \
+                 for (auto& s : segs) {\n        \
+                 p.y += s.a.y + s.b.y;\n        \
+                 p.x += s.a.x + s.b.x;\n    \
+                 }\n    \
+                 return p;\n}\n",
+            ),
+            (
+                Language::Rust,
+                "fn total(segs: Vec<Segment>) -> Point {\n    \
+                 let mut p = Point { y: 0, x: 0 };\n    \
+                 for s in segs {\n        \
+                 p.y += s.a.y + s.b.y;\n        \
+                 p.x += s.a.x + s.b.x;\n    \
+                 }\n    \
+                 p\n}\n",
+            ),
+            (
+                Language::Java,
+                "public class Solution {\n    \
+                 public static Point total(Segment[] segs) {\n        \
+                 long y = 0, x = 0;\n        \
+                 for (Segment s : segs) {\n            \
+                 y += s.a().y() + s.b().y();\n            \
+                 x += s.a().x() + s.b().x();\n        \
+                 }\n        \
+                 return new Point(y, x);\n    \
+                 }\n}\n",
+            ),
+        ];
+
+        let cfg = config_and_set_path().unwrap();
+        let mut config: ExerciseConfig = toml::from_str(
+            r#"
+            title = "test"
+            [types]
+            Segment = "{a: Point, b: Point}"
+            Point = "{y: int, x: int}"
+            Unused = "{n: int}"
+            [functions.total]
+            inputs = ["Segment[]"]
+            output = "Point"
+            visible_cases = 2
+            hidden_cases = 0
+            "#,
+        )
+        .unwrap();
+        let point = |y, x| json!({"y": y, "x": x});
+        let func = config.functions.get_mut("total").unwrap();
+        func.tests = vec![
+            TestCase {
+                inputs: vec![json!([])],
+                output: point(0, 0),
+                hidden: false,
+            },
+            TestCase {
+                inputs: vec![json!([{"a": point(1, 2), "b": point(3, 4)}])],
+                output: point(4, 6),
+                hidden: false,
+            },
+        ];
+
+        for (lang, code) in code {
+            let exercise = exercise(&lang, config.clone());
+            let result = exercise.run_tests(&lang, code, "test", &cfg).unwrap();
+            assert!(result["total"].passed, "{lang:?}: {:?}", result["total"]);
+        }
+
+        let error = toml::from_str::<ExerciseConfig>(
+            "title = \"test\"\n[functions.f]\ninputs = [\"Point\"]\noutput = \"int\"",
+        )
+        .unwrap_err();
+        assert!(
+            error.to_string().contains("Unknown type `Point`"),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn test_checker() {
+        let cfg = config_and_set_path().unwrap();
+        let mut config = simple_config();
+        let func = config.functions.get_mut("test").unwrap();
+        func.case_weights = vec![1.0, 1.0, 2.0, 4.0];
+        func.weight = 2.0;
+        config.check().unwrap();
+
+        // any output smaller than the input is correct
+        let checker = Checker {
+            lang: Language::Python,
+            code: "def check_test(ctx, x, expected, actual):\n    \
+                   ctx.message(f'{actual} < {x}')\n    \
+                   return actual < x\n"
+                .to_string(),
+        };
+        let exercise = Exercise {
+            checker: Some(checker),
+            ..exercise(&Language::Python, config)
+        };
+
+        let code = "def test(x):\n    return x if x == 3 else x - 10\n";
+        let result = exercise
+            .run_tests(&Language::Python, code, "test", &cfg)
+            .unwrap();
+        assert_eq!(
+            result["test"],
+            TestResults {
+                results: vec![
+                    TestResult::Correct {
+                        stdout: "".to_string(),
+                        message: Some("-9 < 1".to_string()),
+                    },
+                    TestResult::Incorrect {
+                        stdout: "".to_string(),
+                        output: json!(3),
+                        message: Some("3 < 3".to_string()),
+                    }
+                ],
+                hidden: HiddenResults {
+                    passed: 2,
+                    total: 2
+                },
+                passed: false,
+                // the second case is 1 of 8
+                score: 0.875,
+                weight: 2.0,
+            }
+        );
+        assert_eq!(total_score(&result), 0.875);
+    }
+
+    #[test]
+    fn test_total_score() {
+        let results = |scores: &[(f64, f64)]| {
+            scores
+                .iter()
+                .enumerate()
+                .map(|(i, &(score, weight))| {
+                    let results = TestResults {
+                        results: Vec::new(),
+                        hidden: HiddenResults {
+                            passed: 0,
+                            total: 0,
+                        },
+                        passed: score == 1.0,
+                        score,
+                        weight,
+                    };
+                    (i.to_string(), results)
+                })
+                .collect::<HashMap<_, _>>()
+        };
+
+        assert_eq!(total_score(&results(&[(1.0, 1.0), (0.5, 3.0)])), 0.625);
+        assert_eq!(total_score(&results(&[(1.0, 0.0), (0.0, 1.0)])), 0.0);
+        assert_eq!(total_score(&results(&[(1.0, 0.0)])), 1.0);
+        assert_eq!(total_score(&results(&[])), 0.0);
+
+        let mut config = simple_config();
+        config.functions.get_mut("test").unwrap().case_weights = vec![1.0];
+        assert!(config.check().is_err());
+        config.functions.get_mut("test").unwrap().case_weights = vec![1.0, 1.0, -1.0, 1.0];
+        assert!(config.check().is_err());
+    }
+
     #[test]
     fn test_generate() {
         let config = config_and_set_path().unwrap();
@@ -373,6 +1501,9 @@ mod test {
         let mut exercise_cfg = ExerciseConfig {
             title: "test".to_string(),
             instructions: "test".to_string(),
+            io: None,
+            types: HashMap::new(),
+            fixtures: BTreeMap::new(),
             functions: HashMap::from_iter([(
                 "test".to_string(),
                 FunctionConfig {
@@ -381,6 +1512,10 @@ mod test {
                     seed: 0,
                     hidden_cases: 2,
                     visible_cases: 2,
+                    compare: Comparator::Exact,
+                    weight: 1.0,
+                    case_weights: Vec::new(),
+                    constraints: None,
                     tests: vec![],
                 },
             )]),
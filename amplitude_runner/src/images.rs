@@ -0,0 +1,136 @@
+//! Building the language images. The hash of each image's build context is
+//! saved when it's built, so images that haven't changed can be skipped.
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use amplitude_common::path;
+use anyhow::Context;
+
+use crate::cache::hash;
+
+/// The state of a language's image
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageState {
+    /// Built from the current build context
+    UpToDate,
+    /// Built, but the build context changed since
+    Changed,
+    /// There is no image with this name
+    Missing,
+}
+
+/// The build context hashes of the images built on this machine
+#[derive(Debug, Default)]
+pub struct BuiltImages(HashMap<String, String>);
+
+impl BuiltImages {
+    fn file() -> PathBuf {
+        path::CACHE.join("images.json")
+    }
+
+    /// Load the saved hashes. Missing or broken files just mean every image
+    /// gets rebuilt.
+    pub fn load() -> Self {
+        let hashes = fs::read(Self::file())
+            .ok()
+            .and_then(|x| serde_json::from_slice(&x).ok())
+            .unwrap_or_default();
+        Self(hashes)
+    }
+
+    pub fn save(&self) -> anyhow::Result<()> {
+        fs::create_dir_all(path::CACHE.as_path()).context("While creating cache dir")?;
+        fs::write(Self::file(), serde_json::to_vec_pretty(&self.0)?)
+            .context("While saving image hashes")
+    }
+
+    /// Check whether the image for language `lang` has to be (re)built
+    pub fn state(&self, command: &str, lang: &str, image: &str) -> anyhow::Result<ImageState> {
+        if !image_exists(command, image) {
+            return Ok(ImageState::Missing);
+        }
+        Ok(match self.0.get(image) == Some(&context_hash(lang)?) {
+            true => ImageState::UpToDate,
+            false => ImageState::Changed,
+        })
+    }
+
+    /// Build the image for language `lang`, with the build output going to our
+    /// stdout and stderr
+    pub fn build(&mut self, command: &str, lang: &str, image: &str) -> anyhow::Result<()> {
+        let hash = context_hash(lang)?;
+        let status = Command::new(command)
+            .args(["build", "-t", image, "."])
+            .current_dir(path::LANGUAGES.join(lang))
+            .status()
+            .with_context(|| format!("While running `{command} build`"))?;
+        anyhow::ensure!(status.success(), "`{command} build` failed with {status}");
+
+        self.0.insert(image.to_string(), hash);
+        Ok(())
+    }
+}
+
+/// The hash of everything in a language's directory that ends up in its image,
+/// which is everything but the templates
+pub fn context_hash(lang: &str) -> anyhow::Result<String> {
+    let dir = path::LANGUAGES.join(lang);
+    let mut files = Vec::new();
+    context_files(&dir, &mut files)
+        .with_context(|| format!("While reading `{}`", dir.display()))?;
+    files.sort();
+
+    let contents = files
+        .iter()
+        .map(|file| fs::read(file).with_context(|| format!("While reading `{}`", file.display())))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let names = files
+        .iter()
+        .map(|file| file.strip_prefix(&dir).unwrap().to_string_lossy())
+        .collect::<Vec<_>>();
+
+    Ok(hash(names.iter().zip(&contents).flat_map(
+        |(name, content)| [name.as_bytes(), content.as_slice()],
+    )))
+}
+
+fn context_files(dir: &Path, out: &mut Vec<PathBuf>) -> anyhow::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            context_files(&path, out)?;
+        } else if path.extension().is_none_or(|x| x != "hbs") {
+            out.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn image_exists(command: &str, image: &str) -> bool {
+    Command::new(command)
+        .args(["image", "inspect", image])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|x| x.success())
+}
+
+#[cfg(test)]
+mod test {
+    use amplitude_common::config_and_set_path;
+
+    use super::*;
+
+    #[test]
+    fn test_context_hash() {
+        config_and_set_path().unwrap();
+        let python = context_hash("python").unwrap();
+        assert_eq!(python, context_hash("python").unwrap());
+        assert_ne!(python, context_hash("rust").unwrap());
+    }
+}
@@ -44,6 +44,18 @@ impl Language {
             Self::Java => "java",
         }
     }
+
+    /// Where the student's code for exercise `id` goes, relative to the
+    /// runner's working directory, so that the runner template can find it.
+    pub fn code_path(&self, id: &str) -> String {
+        match self {
+            // the runner calls static methods on the `Solution` class
+            Self::Java => "Solution.java".to_string(),
+            // next to the runner in `src`
+            Self::C | Self::Cpp | Self::Rust => format!("src/{id}.{}", self.extension()),
+            Self::JavaScript | Self::Python => format!("{id}.{}", self.extension()),
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -0,0 +1,283 @@
+//! Generated test cases are written to a `tests.lock.json` next to each
+//! exercise, and committed with it. Exercises use the test cases in their
+//! lockfile while its source hasn't changed, so they only change on purpose,
+//! and `--check-locks` regenerates them to find generators that drifted.
+//!
+//! The clone of the content repo is managed by git, so lockfiles are only
+//! written next to exercises with `--local`. Otherwise, lockfiles that are out
+//! of date are updated in a copy in the cache, and the committed one is only
+//! read.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use amplitude_common::{config::Config, path};
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    cache::{fixture_parts, hash},
+    exercise::{set_test_cases, ExerciseConfig, TestCase},
+};
+
+/// The name of lockfiles, in the exercise's directory
+pub const LOCKFILE: &str = "tests.lock.json";
+
+/// Bumped when the format of lockfiles changes
+const VERSION: &str = "1";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Lockfile {
+    /// A hash of the sources the test cases were generated from, see
+    /// [`source_hash`]
+    pub source: String,
+    /// Sorted by function name, so the file is the same every time
+    pub tests: BTreeMap<String, Vec<TestCase>>,
+}
+
+impl Lockfile {
+    /// Read the lockfile at `path`, if there is one
+    pub fn load(path: &Path) -> anyhow::Result<Option<Self>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        let src = fs::read_to_string(path).context("While reading lockfile")?;
+        serde_json::from_str(&src)
+            .context("While parsing lockfile")
+            .map(Some)
+    }
+
+    pub fn store(&self, path: &Path) -> anyhow::Result<()> {
+        let mut src = serde_json::to_string_pretty(self)?;
+        src.push('\n');
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).context("While creating lockfile directory")?;
+        }
+        fs::write(path, src).context("While writing lockfile")
+    }
+}
+
+/// Where the lockfile at `path` is written, which is the cache unless the
+/// content is `--local`
+fn store_path(path: &Path, cfg: &Config) -> PathBuf {
+    match cfg.args.local {
+        true => path.to_path_buf(),
+        false => {
+            let key = hash([VERSION.as_bytes(), path.to_string_lossy().as_bytes()]);
+            path::CACHE.join(format!("lock-{key}.json"))
+        }
+    }
+}
+
+/// The hex encoded SHA-256 of what an author writes to get the test cases:
+/// `config.toml` (`config_src`), the generator or reference solution
+/// (`sources`) and the fixtures. The images and templates are left out, since
+/// test cases that change with them are the drift `--check-locks` looks for.
+pub fn source_hash(config_src: &str, sources: &[&str], exercise_cfg: &ExerciseConfig) -> String {
+    hash(
+        [VERSION.as_bytes(), config_src.as_bytes()]
+            .into_iter()
+            .chain(sources.iter().map(|x| x.as_bytes()))
+            .chain(fixture_parts(exercise_cfg)),
+    )
+}
+
+/// Set the test cases of `exercise_cfg` from the lockfile at `path` if it's up
+/// to date, or from `generate`, writing them to the lockfile. Without
+/// `--local`, the copy in the cache is used and written instead of updating
+/// `path`.
+///
+/// With `--check-locks`, the test cases are always generated, and it's an
+/// error if they differ from the lockfile at `path`, which is never written.
+pub fn locked_test_cases(
+    path: &Path,
+    cfg: &Config,
+    config_src: &str,
+    sources: &[&str],
+    exercise_cfg: &mut ExerciseConfig,
+    generate: impl FnOnce(&mut ExerciseConfig) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    let source = source_hash(config_src, sources, exercise_cfg);
+    let load = |path: &Path| -> anyhow::Result<Option<Lockfile>> {
+        let lock =
+            Lockfile::load(path).with_context(|| format!("While loading `{}`", path.display()))?;
+        Ok(lock.filter(|lock| lock.source == source))
+    };
+    let store = store_path(path, cfg);
+    let locked = load(path)?;
+
+    if !cfg.args.check_locks {
+        let lock = match &locked {
+            Some(lock) => Some(lock.clone()),
+            None if store != path => load(&store)?,
+            None => None,
+        };
+        if let Some(lock) = lock {
+            return set_test_cases(exercise_cfg, HashMap::from_iter(lock.tests));
+        }
+    }
+
+    generate(exercise_cfg)?;
+    let generated = Lockfile {
+        source,
+        tests: exercise_cfg
+            .functions
+            .iter()
+            .map(|(name, func)| (name.clone(), func.tests.clone()))
+            .collect(),
+    };
+
+    if !cfg.args.check_locks {
+        return generated
+            .store(&store)
+            .with_context(|| format!("While storing `{}`", store.display()));
+    }
+    let locked = locked.with_context(|| {
+        format!("`{LOCKFILE}` is missing or out of date, run without `--check-locks` to update it")
+    })?;
+    let changes = diff(&locked.tests, &generated.tests);
+    anyhow::ensure!(
+        changes.is_empty(),
+        "The test cases differ from `{LOCKFILE}`:\n{}",
+        changes.join("\n")
+    );
+    Ok(())
+}
+
+/// A line for each test case that was added, removed or changed
+pub fn diff(
+    old: &BTreeMap<String, Vec<TestCase>>,
+    new: &BTreeMap<String, Vec<TestCase>>,
+) -> Vec<String> {
+    let describe = |test: &TestCase| {
+        let inputs = test
+            .inputs
+            .iter()
+            .map(|x| x.to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!("({inputs}) -> {}", test.output)
+    };
+
+    let empty = Vec::new();
+    let mut names = old.keys().chain(new.keys()).collect::<Vec<_>>();
+    names.sort();
+    names.dedup();
+
+    let mut out = Vec::new();
+    for name in names {
+        let old = old.get(name).unwrap_or(&empty);
+        let new = new.get(name).unwrap_or(&empty);
+        for i in 0..old.len().max(new.len()) {
+            match (old.get(i), new.get(i)) {
+                (Some(old), Some(new)) if old == new => {}
+                (Some(old), Some(new)) => out.push(format!(
+                    "`{name}` test case {i}:\n  - {}\n  + {}",
+                    describe(old),
+                    describe(new)
+                )),
+                (Some(old), None) => {
+                    out.push(format!("`{name}` test case {i}:\n  - {}", describe(old)))
+                }
+                (None, Some(new)) => {
+                    out.push(format!("`{name}` test case {i}:\n  + {}", describe(new)))
+                }
+                (None, None) => unreachable!(),
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use amplitude_common::config_and_set_path;
+    use serde_json::json;
+
+    use super::*;
+
+    fn test_case(input: i64, output: i64) -> TestCase {
+        TestCase {
+            inputs: vec![json!(input)],
+            output: json!(output),
+            hidden: false,
+        }
+    }
+
+    #[test]
+    fn test_diff() {
+        let old = BTreeMap::from([
+            ("add".to_string(), vec![test_case(1, 2), test_case(2, 3)]),
+            ("sub".to_string(), vec![test_case(1, 0)]),
+        ]);
+        let new = BTreeMap::from([("add".to_string(), vec![test_case(1, 2), test_case(2, 4)])]);
+
+        assert!(diff(&old, &old).is_empty());
+        assert_eq!(
+            diff(&old, &new),
+            [
+                "`add` test case 1:\n  - (2) -> 3\n  + (2) -> 4",
+                "`sub` test case 0:\n  - (1) -> 0",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_locked_test_cases() {
+        let mut cfg = config_and_set_path().unwrap();
+        cfg.args.local = true;
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(LOCKFILE);
+        let config_src = "title = \"add\"\n[functions.add]\ninputs = [\"int\"]\noutput = \"int\"\n\
+                          visible_cases = 1\nhidden_cases = 0";
+        let mut exercise_cfg: ExerciseConfig = toml::from_str(config_src).unwrap();
+
+        let mut locked = |cfg: &Config, output, source| {
+            locked_test_cases(&path, cfg, config_src, &[source], &mut exercise_cfg, |e| {
+                set_test_cases(
+                    e,
+                    HashMap::from([("add".to_string(), vec![test_case(1, output)])]),
+                )
+            })
+            .map(|_| exercise_cfg.functions["add"].tests[0].output.clone())
+        };
+
+        // generated and locked, then taken from the lockfile
+        assert_eq!(locked(&cfg, 2, "a").unwrap(), json!(2));
+        assert_eq!(locked(&cfg, 3, "a").unwrap(), json!(2));
+        // regenerated when the source changes
+        assert_eq!(locked(&cfg, 3, "b").unwrap(), json!(3));
+
+        cfg.args.check_locks = true;
+        assert_eq!(locked(&cfg, 3, "b").unwrap(), json!(3));
+        assert_eq!(
+            locked(&cfg, 4, "b").unwrap_err().to_string(),
+            "The test cases differ from `tests.lock.json`:\n\
+             `add` test case 0:\n  - (1) -> 3\n  + (1) -> 4"
+        );
+        assert!(locked(&cfg, 3, "c").is_err());
+        // never written in check mode
+        assert_eq!(
+            Lockfile::load(&path).unwrap().unwrap().tests["add"][0].output,
+            json!(3)
+        );
+
+        // without `--local`, the committed lockfile is used while it's up to
+        // date, and otherwise a copy in the cache is written and used
+        cfg.args.check_locks = false;
+        cfg.args.local = false;
+        let store = store_path(&path, &cfg);
+        let _ = fs::remove_file(&store);
+        assert_eq!(locked(&cfg, 4, "b").unwrap(), json!(3));
+        assert_eq!(locked(&cfg, 4, "c").unwrap(), json!(4));
+        assert_eq!(locked(&cfg, 5, "c").unwrap(), json!(4));
+        assert_eq!(
+            Lockfile::load(&path).unwrap().unwrap().tests["add"][0].output,
+            json!(3)
+        );
+        fs::remove_file(&store).unwrap();
+    }
+}
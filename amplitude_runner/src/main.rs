@@ -1,15 +1,158 @@
-use std::env;
-
-use amplitude_runner::rebuild_images;
-
-fn main() {
-    // let binding = env::current_dir().unwrap().to_path_buf();
-    // let dir = binding
-    //     .components()
-    //     .take_while(|c| c.as_os_str() != "amplitude")
-    //     .collect::<PathBuf>()
-    //     .join("amplitude");
-    // env::set_current_dir(&dir).unwrap();
-    env::set_current_dir("..").unwrap();
-    rebuild_images()
+use std::{process::ExitCode, str::FromStr};
+
+use amplitude_common::{config::Config, load_config, set_path};
+use amplitude_runner::{
+    images::{BuiltImages, ImageState},
+    lang::Language,
+    sandbox::container_command,
+    smoke,
+};
+use anyhow::Context;
+use clap::{Parser, Subcommand};
+
+/// Manage the images exercises are run in. Builds every image that changed
+/// when no command is given.
+#[derive(Parser)]
+struct Cli {
+    /// The path of the config file
+    #[arg(long, default_value_t = {"config.toml".to_string()})]
+    config: String,
+    #[command(subcommand)]
+    command: Option<Cmd>,
+}
+
+#[derive(Subcommand)]
+enum Cmd {
+    /// List the configured languages and the state of their images
+    List,
+    /// Build the images of some languages (all of them by default), skipping
+    /// the ones that haven't changed since they were last built
+    Build {
+        languages: Vec<String>,
+        /// Rebuild images even if they haven't changed
+        #[arg(long)]
+        force: bool,
+    },
+    /// Run a program and a test harness in each language, to check that they
+    /// work
+    Smoke { languages: Vec<String> },
+}
+
+fn main() -> anyhow::Result<ExitCode> {
+    let cli = Cli::parse();
+    set_path()?;
+    let cfg = load_config(&cli.config)?;
+
+    match cli.command.unwrap_or(Cmd::Build {
+        languages: Vec::new(),
+        force: false,
+    }) {
+        Cmd::List => list(&cfg),
+        Cmd::Build { languages, force } => build(&cfg, &languages, force),
+        Cmd::Smoke { languages } => smoke(&cfg, &languages),
+    }
+}
+
+/// The configured languages out of `names`, or all of them if it's empty
+fn select(cfg: &Config, names: &[String]) -> anyhow::Result<Vec<String>> {
+    for name in names {
+        anyhow::ensure!(
+            cfg.docker.language_config.contains_key(name),
+            "Language `{name}` is not configured"
+        );
+    }
+    let mut langs = match names.is_empty() {
+        true => cfg.docker.language_config.keys().cloned().collect(),
+        false => names.to_vec(),
+    };
+    langs.sort();
+    langs.dedup();
+    Ok(langs)
+}
+
+fn list(cfg: &Config) -> anyhow::Result<ExitCode> {
+    let command = container_command(&cfg.docker);
+    let images = BuiltImages::load();
+
+    println!("{:<12} {:<16} image", "language", "image name");
+    for lang in select(cfg, &[])? {
+        let image = &cfg.docker.language_config[&lang].image_name;
+        let state = match &command {
+            Some(command) => match images.state(command, &lang, image)? {
+                ImageState::UpToDate => "up to date",
+                ImageState::Changed => "changed",
+                ImageState::Missing => "not built",
+            },
+            None => "-",
+        };
+        println!("{lang:<12} {image:<16} {state}");
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn build(cfg: &Config, names: &[String], force: bool) -> anyhow::Result<ExitCode> {
+    let command = container_command(&cfg.docker)
+        .context("The local backend doesn't use images, there's nothing to build")?;
+    let mut images = BuiltImages::load();
+
+    let mut failed = Vec::new();
+    for lang in select(cfg, names)? {
+        let image = &cfg.docker.language_config[&lang].image_name;
+        if !force && images.state(&command, &lang, image)? == ImageState::UpToDate {
+            println!("`{image}` is up to date");
+            continue;
+        }
+
+        println!("Building `{image}`");
+        match images.build(&command, &lang, image) {
+            // saved after every image, so the ones that were built are
+            // skipped next time even if a later one fails
+            Ok(()) => images.save()?,
+            Err(e) => {
+                eprintln!("Error building `{image}`: {e:?}");
+                failed.push(lang);
+            }
+        }
+    }
+
+    if failed.is_empty() {
+        return Ok(ExitCode::SUCCESS);
+    }
+    eprintln!("Failed to build: {}", failed.join(", "));
+    Ok(ExitCode::FAILURE)
+}
+
+fn smoke(cfg: &Config, names: &[String]) -> anyhow::Result<ExitCode> {
+    let mut errors = Vec::new();
+    let mut rows = Vec::new();
+    for name in select(cfg, names)? {
+        let Ok(lang) = Language::from_str(&name) else {
+            rows.push((name, "unsupported", "unsupported"));
+            continue;
+        };
+
+        let mut check = |test: &str, result: anyhow::Result<()>| match result {
+            Ok(()) => "ok",
+            Err(e) => {
+                errors.push(format!("{name} ({test}): {e:?}"));
+                "FAILED"
+            }
+        };
+        let hello = check("hello world", smoke::hello_world(&lang, cfg));
+        let harness = check("harness", smoke::harness(&lang, cfg));
+        rows.push((name, hello, harness));
+    }
+
+    for error in &errors {
+        eprintln!("{error}\n");
+    }
+    println!("{:<12} {:<12} harness", "language", "hello world");
+    for (lang, hello, harness) in rows {
+        println!("{lang:<12} {hello:<12} {harness}");
+    }
+
+    Ok(match errors.is_empty() {
+        true => ExitCode::SUCCESS,
+        false => ExitCode::FAILURE,
+    })
 }
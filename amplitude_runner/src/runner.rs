@@ -1,8 +1,9 @@
 use std::{
     collections::HashMap,
+    fmt::Write as _,
     fs,
     io::BufRead,
-    process::{Command, Stdio},
+    os::unix::process::ExitStatusExt,
     time::{Duration, Instant},
 };
 
@@ -10,6 +11,8 @@ use amplitude_common::config::{DockerConfig, LanguageConfig};
 use anyhow::Context;
 use serde::{Deserialize, Serialize};
 
+use crate::sandbox::{sandbox, Job, OOM_MARKER};
+
 pub fn url_encode(url: &str) -> String {
     const ALLOWED_CHARS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ\
                                    abcdefghijklmnopqrstuvwxyz\
@@ -34,8 +37,60 @@ pub struct RunOutput {
     pub stderr: String,
     pub runtime: Duration,
     pub exit_code: i32,
+    pub status: ExitStatus,
+}
+
+/// How the process inside the container ended
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExitStatus {
+    /// Exited normally with code 0
+    Success,
+    /// Exited normally with a non-zero code
+    Failure,
+    /// Killed for running longer than `DockerConfig::timeout`
+    Timeout,
+    /// Killed for going over the memory limit
+    OutOfMemory,
+    /// Killed for writing past the file size limit (`SIGXFSZ`), or printed
+    /// more than `OutputConfig::run`
+    OutputLimit,
+    /// Killed by some other signal
+    Signal(i32),
+}
+
+impl ExitStatus {
+    /// Figure out why a process ended from its exit code.
+    ///
+    /// Shells (and docker) report a process killed by signal `n` as exiting
+    /// with `128 + n`. `timeout --signal=KILL` kills itself with the same
+    /// signal it sent, so a timeout and the OOM killer both show up as
+    /// `SIGKILL` (137), and are told apart by what the sandbox reported:
+    /// `timed_out` if the timer in `run.sh` fired, `oom_killed` if the OOM
+    /// killer killed something. The CPU time limit of the local backend sends
+    /// `SIGXCPU` instead, which is a timeout too.
+    pub fn from_exit_code(exit_code: i32, timed_out: bool, oom_killed: bool) -> Self {
+        const SIGKILL: i32 = 9;
+        const SIGXCPU: i32 = 24;
+        const SIGXFSZ: i32 = 25;
+
+        match exit_code {
+            0 => Self::Success,
+            // `timeout` without `--signal=KILL`
+            124 if timed_out => Self::Timeout,
+            c if c == 128 + SIGKILL && timed_out => Self::Timeout,
+            c if c == 128 + SIGKILL && oom_killed => Self::OutOfMemory,
+            c if c == 128 + SIGXCPU => Self::Timeout,
+            c if c == 128 + SIGXFSZ => Self::OutputLimit,
+            c if c > 128 && c < 128 + 32 => Self::Signal(c - 128),
+            _ => Self::Failure,
+        }
+    }
 }
 
+/// Printed to stderr by `timeout --verbose` when it kills a command
+const TIMEOUT_MARKER: &str = "timeout: sending signal KILL to command";
+
 pub fn run(
     lang: &LanguageConfig,
     cfg: &DockerConfig,
@@ -43,30 +98,18 @@ pub fn run(
     other_files: HashMap<String, &[u8]>,
     args: &str,
 ) -> anyhow::Result<RunOutput> {
-    // test that the docker image exists
-    #[cfg(debug_assertions)]
-    {
-        let out = Command::new(&cfg.command)
-            .arg("images")
-            .arg(&lang.image_name)
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .spawn()
-            .unwrap()
-            .wait_with_output()
-            .context("While running `docker images`")?;
-        if out
-            .stdout
-            .lines()
-            .skip(1)
-            .filter_map(|x| x.ok())
-            .all(|x| !x.starts_with(&lang.image_name))
-        {
-            eprintln!("Image {} not found! try running `cargo r -p amplitude_runner` to rebuild docker images", &lang.image_name);
-            std::process::exit(-1);
-        }
-    }
+    run_with_stdin(lang, cfg, src, other_files, args, &[])
+}
 
+/// [`run`], feeding `stdin` to the program
+pub fn run_with_stdin(
+    lang: &LanguageConfig,
+    cfg: &DockerConfig,
+    src: &str,
+    other_files: HashMap<String, &[u8]>,
+    args: &str,
+    stdin: &[u8],
+) -> anyhow::Result<RunOutput> {
     let tempdir = tempfile::tempdir_in(&cfg.tmp_folder).context("While creating temp dir")?;
     let code_path = tempdir.path().join(&lang.source_path);
     fs::create_dir_all(code_path.parent().unwrap()).context("While creating temp dir")?;
@@ -78,75 +121,90 @@ pub fn run(
         fs::write(&path, content).context("While writing file")?;
     }
 
+    let job = Job {
+        lang,
+        dir: tempdir.path(),
+        files: [lang.source_path.as_str()]
+            .into_iter()
+            .chain(other_files.keys().map(|x| x.as_str()))
+            .collect(),
+        args,
+        stdin,
+        timeout: cfg.timeout,
+        max_output: cfg.output.run,
+    };
+
     let time = Instant::now();
+    let run = sandbox(cfg).run(&job).context("While running code")?;
+    let runtime = time.elapsed();
+    // if the sandbox itself was killed there is no exit code
+    let exit_code = run
+        .status
+        .code()
+        .or_else(|| run.status.signal().map(|s| 128 + s))
+        .unwrap_or(-1);
 
-    let v = [&lang.source_path]
-        .into_iter()
-        .chain(other_files.keys())
-        .map(|path| {
-            format!(
-                "{}:/runner/{}",
-                tempdir.path().join(path).to_string_lossy(),
-                &path
-            )
-        })
-        .collect::<Vec<_>>();
-
-    // tried to use bollard instead of using a command but that was even worse
-    let run = Command::new(&cfg.command)
-        .args(
-            [
-                "run",
-                "--rm",
-                "--cap-drop=ALL",
-                "--security-opt=no-new-privileges",
-                "--net",
-                "none",
-                "--memory",
-                "128m",
-                "--memory-swap",
-                "128m",
-                "--pids-limit",
-                "512",
-                "-e",
-                &format!("TIMEOUT={}", &cfg.timeout),
-                "-e",
-                &format!("ARGS={}", url_encode(args)),
-                "-v",
-            ]
-            .into_iter()
-            .chain(v.iter().map(|x| x.as_str()).intersperse("-v"))
-            .chain([lang.image_name.as_str()]),
-        )
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .spawn()
-        .unwrap()
-        .wait_with_output()
-        .unwrap();
+    let mut stderr = run.stderr;
+    let timed_out = stderr
+        .lines()
+        .any(|line| line.is_ok_and(|x| x.starts_with(TIMEOUT_MARKER)));
+    let oom_marker = format!("{OOM_MARKER}\n");
+    let oom_killed = stderr.ends_with(oom_marker.as_bytes());
+    if oom_killed {
+        stderr.truncate(stderr.len() - oom_marker.len());
+    }
+
+    let max = cfg.output.run;
+    let status = match run.stdout.len() > max || stderr.len() > max {
+        true => ExitStatus::OutputLimit,
+        false => ExitStatus::from_exit_code(exit_code, timed_out, oom_killed),
+    };
+    // only the first `max + 1` bytes were kept, so how much was cut off isn't
+    // known
+    let output = |bytes: &[u8]| {
+        let mut out = String::from_utf8_lossy(&bytes[..bytes.len().min(max)]).to_string();
+        if bytes.len() > max {
+            out.push_str("\n[... output limit reached]");
+        }
+        out
+    };
 
     Ok(RunOutput {
-        stdout: String::from_utf8_lossy(&run.stdout).to_string(),
-        stderr: String::from_utf8_lossy(&run.stderr).to_string(),
-        runtime: time.elapsed(),
-        exit_code: run.status.code().unwrap(),
+        stdout: output(&run.stdout),
+        stderr: output(&stderr),
+        runtime,
+        exit_code,
+        status,
     })
 }
 
+/// Cut `s` down to its first `max` bytes, with a note saying how much was cut
+/// off
+pub fn truncate(s: &mut String, max: usize) {
+    if s.len() <= max {
+        return;
+    }
+    let mut end = max;
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    let cut = s.len() - end;
+    s.truncate(end);
+    let _ = write!(s, "\n[... {cut} more bytes]");
+}
+
 #[cfg(test)]
 mod test {
-    use std::{env, fs};
-
-    use amplitude_common::config::{Args, Config};
+    use amplitude_common::{
+        config::{Backend, Config},
+        config_and_set_path,
+    };
 
     use super::*;
 
     #[test]
     fn test_runner() -> anyhow::Result<()> {
-        env::set_current_dir("../")?;
-        let args = Args::parse();
-        let mut config: Config = toml::from_str::<Config>(&fs::read_to_string(&args.config)?)?;
-        config.args = args;
+        let config = config_and_set_path()?;
 
         let output = run(
             config
@@ -171,4 +229,115 @@ file.close()
 
         Ok(())
     }
+
+    #[test]
+    fn test_output_limit() {
+        let mut config = config_and_set_path().unwrap();
+        config.docker.output.run = 100;
+        let lang = config.docker.language_config.get("python").unwrap();
+
+        let output = run(
+            lang,
+            &config.docker,
+            "print('a' * 1000)",
+            HashMap::new(),
+            "",
+        )
+        .unwrap();
+        assert_eq!(output.status, ExitStatus::OutputLimit);
+        assert_eq!(
+            output.stdout,
+            format!("{}\n[... output limit reached]", "a".repeat(100))
+        );
+
+        let output = run(lang, &config.docker, "print('a' * 99)", HashMap::new(), "").unwrap();
+        assert_eq!(output.status, ExitStatus::Success);
+    }
+
+    #[test]
+    fn test_truncate() {
+        let mut s = "héllo".to_string();
+        truncate(&mut s, 2);
+        assert_eq!(s, "h\n[... 5 more bytes]");
+        let mut s = "hello".to_string();
+        truncate(&mut s, 5);
+        assert_eq!(s, "hello");
+    }
+
+    #[test]
+    fn test_exit_status() {
+        assert_eq!(
+            ExitStatus::from_exit_code(0, false, false),
+            ExitStatus::Success
+        );
+        assert_eq!(
+            ExitStatus::from_exit_code(1, false, false),
+            ExitStatus::Failure
+        );
+        assert_eq!(
+            ExitStatus::from_exit_code(124, true, false),
+            ExitStatus::Timeout
+        );
+        assert_eq!(
+            ExitStatus::from_exit_code(137, true, false),
+            ExitStatus::Timeout
+        );
+        assert_eq!(
+            ExitStatus::from_exit_code(137, false, true),
+            ExitStatus::OutOfMemory
+        );
+        // killed by something else
+        assert_eq!(
+            ExitStatus::from_exit_code(137, false, false),
+            ExitStatus::Signal(9)
+        );
+        assert_eq!(
+            ExitStatus::from_exit_code(152, false, false),
+            ExitStatus::Timeout
+        );
+        assert_eq!(
+            ExitStatus::from_exit_code(153, false, false),
+            ExitStatus::OutputLimit
+        );
+        assert_eq!(
+            ExitStatus::from_exit_code(139, false, false),
+            ExitStatus::Signal(11)
+        );
+    }
+
+    #[test]
+    fn test_killed() {
+        let mut config = config_and_set_path().unwrap();
+        config.docker.timeout = 1;
+        let run = |config: &Config, lang: &str, code: &str| {
+            let lang = &config.docker.language_config[lang];
+            run(lang, &config.docker, code, HashMap::new(), "").unwrap()
+        };
+
+        let output = run(&config, "python", "while True: pass");
+        assert_eq!(output.status, ExitStatus::Timeout);
+        let output = run(&config, "python", "import os\nos.kill(os.getpid(), 9)");
+        assert_eq!(output.status, ExitStatus::Signal(9));
+
+        // the local backend has the same file size limit as the containers
+        config.docker.backend = Backend::Local;
+        let output = run(
+            &config,
+            "c",
+            "#include <stdio.h>\n\
+             int main() {\n    \
+                 FILE *f = fopen(\"big\", \"w\");\n    \
+                 for (int i = 0; i < 100 << 20; i++) fputc('0', f);\n\
+             }\n",
+        );
+        assert_eq!(output.status, ExitStatus::OutputLimit);
+
+        // what the cpu time limit of the local backend sends
+        let output = run(
+            &config,
+            "python",
+            "import os, signal\nos.kill(os.getpid(), signal.SIGXCPU)",
+        );
+        assert_eq!(output.status, ExitStatus::Timeout);
+    }
 }
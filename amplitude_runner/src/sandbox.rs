@@ -0,0 +1,133 @@
+//! The backends that actually run code: containers (docker / podman) or plain
+//! local processes.
+
+use std::{
+    io::{self, Read, Write},
+    path::Path,
+    process::{Command, Output, Stdio},
+    sync::OnceLock,
+    thread,
+};
+
+use amplitude_common::config::{Backend, DockerConfig, LanguageConfig};
+
+mod container;
+mod local;
+mod pool;
+
+pub use container::Container;
+pub use local::Local;
+pub use pool::Pool;
+
+/// There is only one pool, shared by everything that runs code
+static POOL: OnceLock<Pool> = OnceLock::new();
+
+/// Printed to stderr when something in a container was killed by the OOM
+/// killer while running a job
+pub(crate) const OOM_MARKER: &str = "amplitude: killed for using too much memory";
+
+/// The script that runs `run.sh` in a container. A process killed by the OOM
+/// killer looks like one killed by anything else, so the cgroup's count of OOM
+/// kills (v2 or v1) is checked before and after.
+fn run_script() -> String {
+    format!(
+        "oom() {{ cat /sys/fs/cgroup/memory.events /sys/fs/cgroup/memory/memory.oom_control \
+         2> /dev/null | sed -n 's/^oom_kill //p'; }}\n\
+         before=$(oom); bash ./run.sh; code=$?\n\
+         [ \"$(oom)\" = \"$before\" ] || echo '{OOM_MARKER}' >&2\n\
+         exit $code"
+    )
+}
+
+/// Everything a sandbox needs to know to run some code
+pub struct Job<'a> {
+    pub lang: &'a LanguageConfig,
+    /// The directory the job's files were written to
+    pub dir: &'a Path,
+    /// The job's files, relative to `dir`
+    pub files: Vec<&'a str>,
+    /// Arguments passed to the program
+    pub args: &'a str,
+    /// Fed to the program's stdin
+    pub stdin: &'a [u8],
+    /// Time limit in seconds, `0` for none
+    pub timeout: u64,
+    /// How many bytes of stdout and of stderr are kept, see
+    /// [`output_with_stdin`]
+    pub max_output: usize,
+}
+
+pub trait Sandbox: Send + Sync {
+    /// Run the language's `run.sh` with the job's files next to it and wait
+    /// for it to exit.
+    fn run(&self, job: &Job) -> anyhow::Result<Output>;
+}
+
+/// Get the sandbox selected in the config. The first call with a `pool`
+/// configured starts the pool.
+pub fn sandbox(cfg: &DockerConfig) -> Box<dyn Sandbox> {
+    let Some(command) = container_command(cfg) else {
+        return Box::new(Local);
+    };
+
+    match &cfg.pool {
+        Some(pool) => {
+            let pool = POOL.get_or_init(|| Pool::new(cfg, command, pool.clone()));
+            pool.start();
+            Box::new(pool)
+        }
+        None => Box::new(Container::new(command)),
+    }
+}
+
+/// The command used to manage containers, or `None` for the local backend
+pub fn container_command(cfg: &DockerConfig) -> Option<String> {
+    let command = match cfg.backend {
+        Backend::Docker => "docker",
+        Backend::Podman => "podman",
+        Backend::Local => return None,
+    };
+    Some(cfg.command.clone().unwrap_or_else(|| command.to_string()))
+}
+
+/// Run a command with `stdin` as its input and collect its output. Only the
+/// first `max_output + 1` bytes of stdout and stderr are kept, so output over
+/// the limit can be told apart, and the rest is thrown away as it's read.
+fn output_with_stdin(command: &mut Command, stdin: &[u8], max_output: usize) -> io::Result<Output> {
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let mut pipe = child.stdin.take().unwrap();
+    let stdout = child.stdout.take().unwrap();
+    let stderr = child.stderr.take().unwrap();
+
+    // written from another thread, so a program that doesn't read all of its
+    // input (or writes a lot before reading it) can't block us
+    thread::scope(|s| {
+        s.spawn(move || {
+            // the program is allowed to exit without reading everything
+            let _ = pipe.write_all(stdin);
+        });
+        let stdout = s.spawn(move || read_capped(stdout, max_output));
+        let stderr = s.spawn(move || read_capped(stderr, max_output));
+
+        let status = child.wait()?;
+        Ok(Output {
+            status,
+            stdout: stdout.join().unwrap()?,
+            stderr: stderr.join().unwrap()?,
+        })
+    })
+}
+
+/// Read all of `pipe`, keeping the first `max + 1` bytes
+fn read_capped(mut pipe: impl Read, max: usize) -> io::Result<Vec<u8>> {
+    let mut out = Vec::new();
+    (&mut pipe)
+        .take(max.saturating_add(1) as u64)
+        .read_to_end(&mut out)?;
+    io::copy(&mut pipe, &mut io::sink())?;
+    Ok(out)
+}
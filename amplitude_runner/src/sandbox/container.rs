@@ -0,0 +1,96 @@
+use std::{
+    io::BufRead,
+    process::{Command, Output, Stdio},
+};
+
+use anyhow::Context;
+
+use super::{output_with_stdin, run_script, Job, Sandbox};
+use crate::runner::url_encode;
+
+/// Flags that isolate a container and limit its resources
+pub(super) const LIMITS: [&str; 12] = [
+    "--cap-drop=ALL",
+    "--security-opt=no-new-privileges",
+    "--net",
+    "none",
+    "--memory",
+    "128m",
+    "--memory-swap",
+    "128m",
+    "--pids-limit",
+    "512",
+    // 64 MiB, like `MAX_FILE_SIZE` in the local backend
+    "--ulimit",
+    "fsize=67108864",
+];
+
+/// Runs jobs in a fresh container with a docker compatible cli (docker or
+/// podman)
+pub struct Container {
+    command: String,
+}
+
+impl Container {
+    pub fn new(command: String) -> Self {
+        Self { command }
+    }
+}
+
+impl Sandbox for Container {
+    fn run(&self, job: &Job) -> anyhow::Result<Output> {
+        let lang = job.lang;
+
+        // test that the image exists
+        #[cfg(debug_assertions)]
+        {
+            let out = Command::new(&self.command)
+                .arg("images")
+                .arg(&lang.image_name)
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped())
+                .spawn()
+                .with_context(|| format!("While running `{} images`", self.command))?
+                .wait_with_output()
+                .with_context(|| format!("While running `{} images`", self.command))?;
+            if out
+                .stdout
+                .lines()
+                .skip(1)
+                .filter_map(|x| x.ok())
+                .all(|x| !x.starts_with(&lang.image_name))
+            {
+                eprintln!("Image {} not found! try running `cargo r -p amplitude_runner` to rebuild docker images", lang.image_name);
+                std::process::exit(-1);
+            }
+        }
+
+        // mounts need an absolute path
+        let dir = job.dir.canonicalize().context("While resolving temp dir")?;
+        let v = job
+            .files
+            .iter()
+            .map(|path| format!("{}:/runner/{}", dir.join(path).to_string_lossy(), path))
+            .collect::<Vec<_>>();
+
+        // tried to use bollard instead of using a command but that was even worse
+        let mut command = Command::new(&self.command);
+        command.args(
+            ["run", "--rm", "-i"]
+                .into_iter()
+                .chain(LIMITS)
+                .chain([
+                    "-e",
+                    &format!("TIMEOUT={}", job.timeout),
+                    "-e",
+                    &format!("ARGS={}", url_encode(job.args)),
+                    "-v",
+                ])
+                .chain(v.iter().map(|x| x.as_str()).intersperse("-v"))
+                .chain(["--entrypoint", "bash", lang.image_name.as_str(), "-c"])
+                .chain([run_script().as_str()]),
+        );
+        output_with_stdin(&mut command, job.stdin, job.max_output)
+            .with_context(|| format!("While running `{} run`", self.command))
+    }
+}
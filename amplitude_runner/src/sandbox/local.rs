@@ -0,0 +1,113 @@
+use std::{
+    fs,
+    process::{Command, Output},
+};
+
+use amplitude_common::path;
+use anyhow::Context;
+
+use super::{output_with_stdin, Job, Sandbox};
+use crate::runner::url_encode;
+
+/// Memory a job can use in bytes. This limits the data segment (`ulimit -d`)
+/// instead of the address space (`ulimit -v`), since the JVM and V8 reserve a
+/// lot more address space than they use.
+const MAX_MEMORY: u64 = 512 << 20;
+/// Processes a job can start. This counts every process of the user running
+/// the server, not just the job's.
+const MAX_PROCESSES: u64 = 4096;
+/// The largest file a job can write in bytes, the container backends use the
+/// same limit
+const MAX_FILE_SIZE: u64 = 64 << 20;
+
+/// Runs jobs as a plain process on this machine, with the language's
+/// toolchain installed locally. Nothing stops the code from touching the rest
+/// of the system, so this is only meant for development.
+pub struct Local;
+
+impl Sandbox for Local {
+    fn run(&self, job: &Job) -> anyhow::Result<Output> {
+        // everything the image would have added next to the job's files
+        let lang_dir = path::LANGUAGES.join(&job.lang.name);
+        for entry in fs::read_dir(&lang_dir)
+            .with_context(|| format!("While reading `{}`", lang_dir.display()))?
+        {
+            let entry = entry?;
+            let path = entry.path();
+            let skip = !entry.file_type()?.is_file()
+                || entry.file_name() == "Dockerfile"
+                || path.extension().is_some_and(|ext| ext == "hbs");
+            let to = job.dir.join(entry.file_name());
+            if skip || to.exists() {
+                continue;
+            }
+            fs::copy(&path, &to).with_context(|| format!("While copying `{}`", path.display()))?;
+        }
+
+        // no core dumps, and cpu time as a backup for `timeout` in `run.sh`
+        let mut limits = format!(
+            "ulimit -c 0 && ulimit -d {} && ulimit -u {MAX_PROCESSES} && ulimit -f {}",
+            MAX_MEMORY / 1024,
+            MAX_FILE_SIZE / 1024
+        );
+        if job.timeout != 0 {
+            limits += &format!(" && ulimit -t {}", job.timeout);
+        }
+
+        let mut command = Command::new("bash");
+        command
+            .arg("-c")
+            .arg(format!("{limits} && exec bash ./run.sh"))
+            .current_dir(job.dir)
+            .env("TIMEOUT", job.timeout.to_string())
+            .env("ARGS", url_encode(job.args));
+        output_with_stdin(&mut command, job.stdin, job.max_output).context("While running `run.sh`")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use amplitude_common::{config::Backend, config_and_set_path};
+
+    use crate::runner::run;
+
+    #[test]
+    fn test_local() {
+        let mut config = config_and_set_path().unwrap();
+        config.docker.backend = Backend::Local;
+
+        let output = run(
+            &config.docker.language_config["python"],
+            &config.docker,
+            "print(open('file.txt').read())",
+            HashMap::from_iter([("file.txt".to_string(), "File contents".as_bytes())]),
+            "",
+        )
+        .unwrap();
+        assert_eq!(output.stdout, "File contents\n");
+    }
+
+    #[test]
+    fn test_local_limits() {
+        let mut config = config_and_set_path().unwrap();
+        config.docker.backend = Backend::Local;
+        let python = |code| {
+            run(
+                &config.docker.language_config["python"],
+                &config.docker,
+                code,
+                HashMap::new(),
+                "",
+            )
+            .unwrap()
+        };
+
+        let output = python("x = bytearray(1 << 30)");
+        assert!(output.stderr.contains("MemoryError"), "{output:?}");
+        // python ignores `SIGXFSZ`, so the write fails instead
+        let output = python("open('big', 'wb').write(bytes(100 << 20))");
+        assert!(output.stderr.contains("File too large"), "{output:?}");
+    }
+}
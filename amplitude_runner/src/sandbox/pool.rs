@@ -0,0 +1,397 @@
+use std::{
+    collections::HashMap,
+    fs,
+    os::unix::fs::PermissionsExt,
+    path::Path,
+    process::{Command, Output, Stdio},
+    sync::{Mutex, Once},
+    thread,
+};
+
+use amplitude_common::config::{DockerConfig, PoolConfig};
+use anyhow::Context;
+use tempfile::TempDir;
+
+use super::{container::LIMITS, output_with_stdin, run_script, Container, Job, Sandbox};
+use crate::runner::url_encode;
+
+/// Label on every pooled container, so the ones left behind by a previous run
+/// can be cleaned up
+const LABEL: &str = "amplitude.pool";
+
+/// Flags that keep everything but `/job` and the temp directories read-only
+const READ_ONLY: [&str; 7] = [
+    "--read-only",
+    "--tmpfs",
+    "/tmp:exec",
+    "--tmpfs",
+    "/var/tmp",
+    "-e",
+    "HOME=/tmp",
+];
+
+/// Wipes everything a job could have written to, `rm` only deletes what the
+/// job's user owns
+const WIPE: &str = "kill -9 -1; \
+    for dir in /job /tmp /var/tmp /dev/shm /run; do rm -rf $dir/* $dir/.[!.]*; done; \
+    true";
+
+/// The idle containers for an image
+#[derive(Default)]
+struct Idle {
+    warm: Vec<Warm>,
+    /// Containers that are being started in the background
+    starting: usize,
+}
+
+/// A container idling in the background, with a directory from the host
+/// mounted at `/job`
+struct Warm {
+    id: String,
+    dir: TempDir,
+    uses: u32,
+}
+
+/// Keeps containers for each language running in the background and runs
+/// jobs in them with `exec`, so a job doesn't have to wait for a container to
+/// start.
+///
+/// Containers are read-only except for `/job` and the temp directories, and
+/// `$HOME` is moved to `/tmp`, so a job can't leave anything behind for the
+/// next one outside of what's wiped. Before a container is handed out it is
+/// health checked, and after a job its processes are killed and the writable
+/// directories are wiped. Once it has run `max_uses` jobs it is removed and
+/// replaced.
+pub struct Pool {
+    command: String,
+    tmp_folder: String,
+    cfg: PoolConfig,
+    /// The images to keep containers for
+    images: Vec<String>,
+    /// Idle containers for each image
+    idle: Mutex<HashMap<String, Idle>>,
+    started: Once,
+}
+
+impl Pool {
+    pub fn new(docker: &DockerConfig, command: String, cfg: PoolConfig) -> Self {
+        let images = docker
+            .language_config
+            .iter()
+            .filter(|(name, _)| cfg.languages.is_empty() || cfg.languages.contains(name))
+            .map(|(_, lang)| lang.image_name.clone())
+            .collect();
+
+        Self {
+            command,
+            tmp_folder: docker.tmp_folder.clone(),
+            cfg,
+            images,
+            idle: Mutex::new(HashMap::new()),
+            started: Once::new(),
+        }
+    }
+
+    /// Remove containers left behind by a previous run and fill the pool in
+    /// the background. Only does anything the first time it's called.
+    pub fn start(&'static self) {
+        self.started.call_once(|| {
+            if let Err(e) = self.remove_stale() {
+                eprintln!("Error removing old pooled containers: {e:?}");
+            }
+            for image in &self.images {
+                self.refill(image);
+            }
+        });
+    }
+
+    fn remove_stale(&self) -> anyhow::Result<()> {
+        let out = Command::new(&self.command)
+            .args(["ps", "-aq", "--filter", &format!("label={LABEL}")])
+            .output()
+            .with_context(|| format!("While running `{} ps`", self.command))?;
+        let ids = String::from_utf8_lossy(&out.stdout);
+        for id in ids.split_whitespace() {
+            self.remove(id);
+        }
+        Ok(())
+    }
+
+    /// Start containers in the background until there are `size` idle ones
+    fn refill(&'static self, image: &str) {
+        let mut idle = self.idle.lock().unwrap();
+        let idle = idle.entry(image.to_string()).or_default();
+        let missing = self
+            .cfg
+            .size
+            .saturating_sub(idle.warm.len() + idle.starting);
+        idle.starting += missing;
+
+        for _ in 0..missing {
+            let image = image.to_string();
+            thread::spawn(move || {
+                let warm = self.start_container(&image);
+                if let Some(idle) = self.idle.lock().unwrap().get_mut(&image) {
+                    idle.starting -= 1;
+                }
+                match warm {
+                    Ok(warm) => self.checkin(&image, warm),
+                    Err(e) => eprintln!("Error starting pooled container for `{image}`: {e:?}"),
+                }
+            });
+        }
+    }
+
+    fn start_container(&self, image: &str) -> anyhow::Result<Warm> {
+        let dir = tempfile::tempdir_in(&self.tmp_folder).context("While creating temp dir")?;
+        // the container doesn't run as the same user as us
+        fs::set_permissions(dir.path(), fs::Permissions::from_mode(0o777))?;
+        let mount = format!(
+            "{}:/job",
+            dir.path()
+                .canonicalize()
+                .context("While resolving temp dir")?
+                .to_string_lossy()
+        );
+
+        // `sleep` is pid 1, so it survives `kill -1` when the container is wiped
+        let out = Command::new(&self.command)
+            .args(["run", "-d", "--rm", "--label", LABEL])
+            .args(LIMITS)
+            .args(READ_ONLY)
+            .args(["-v", &mount, "--entrypoint", "sleep", image, "infinity"])
+            .output()
+            .with_context(|| format!("While running `{} run`", self.command))?;
+        anyhow::ensure!(
+            out.status.success(),
+            "Failed to start container: {}",
+            String::from_utf8_lossy(&out.stderr)
+        );
+
+        Ok(Warm {
+            id: String::from_utf8_lossy(&out.stdout).trim().to_string(),
+            dir,
+            uses: 0,
+        })
+    }
+
+    /// Take a healthy idle container, or start a new one if there aren't any
+    fn checkout(&'static self, image: &str) -> anyhow::Result<Warm> {
+        let warm = loop {
+            let Some(warm) = self
+                .idle
+                .lock()
+                .unwrap()
+                .get_mut(image)
+                .and_then(|x| x.warm.pop())
+            else {
+                break None;
+            };
+            if self.healthy(&warm) {
+                break Some(warm);
+            }
+            self.remove(&warm.id);
+        };
+
+        self.refill(image);
+        match warm {
+            Some(warm) => Ok(warm),
+            None => self.start_container(image),
+        }
+    }
+
+    /// Put a container back in the pool, unless it's already full
+    fn checkin(&self, image: &str, warm: Warm) {
+        let mut idle = self.idle.lock().unwrap();
+        let idle = idle.entry(image.to_string()).or_default();
+        match idle.warm.len() < self.cfg.size {
+            true => idle.warm.push(warm),
+            false => self.remove(&warm.id),
+        }
+    }
+
+    fn healthy(&self, warm: &Warm) -> bool {
+        self.exec(&warm.id, &[], "true", &[], 0)
+            .is_ok_and(|out| out.status.success())
+    }
+
+    /// Get a used container ready for the next job, or remove it if it has
+    /// run enough jobs (or can't be cleaned up)
+    fn recycle(&self, image: &str, mut warm: Warm) {
+        warm.uses += 1;
+        if warm.uses >= self.cfg.max_uses {
+            self.remove(&warm.id);
+            return;
+        }
+
+        // kill everything the job left running (everything but `sleep` and
+        // this shell) and delete its files
+        let wiped = self
+            .exec(&warm.id, &[], WIPE, &[], 0)
+            .is_ok_and(|out| out.status.success());
+        match wiped && clear_dir(warm.dir.path()).is_ok() {
+            true => self.checkin(image, warm),
+            false => self.remove(&warm.id),
+        }
+    }
+
+    /// Run a job in a container that was checked out
+    fn run_in(&self, warm: &Warm, job: &Job) -> anyhow::Result<Output> {
+        copy_files(job, warm.dir.path())?;
+        // everything the image adds to its working directory goes next to the
+        // job's files, like when the files are mounted into it
+        self.exec(
+            &warm.id,
+            &[
+                format!("TIMEOUT={}", job.timeout),
+                format!("ARGS={}", url_encode(job.args)),
+            ],
+            // some versions of `cp -n` fail when they skip a file
+            &format!("cp -rn /runner/. /job/ 2> /dev/null; {}", run_script()),
+            job.stdin,
+            job.max_output,
+        )
+    }
+
+    fn remove(&self, id: &str) {
+        let _ = Command::new(&self.command)
+            .args(["rm", "-f", id])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status();
+    }
+
+    /// Run `script` in a container, see [`output_with_stdin`] for `max_output`
+    fn exec(
+        &self,
+        id: &str,
+        env: &[String],
+        script: &str,
+        stdin: &[u8],
+        max_output: usize,
+    ) -> anyhow::Result<Output> {
+        let mut command = Command::new(&self.command);
+        command
+            .args(["exec", "-i"])
+            .args(env.iter().flat_map(|x| ["-e", x.as_str()]))
+            .args(["-w", "/job", id, "bash", "-c", script]);
+        output_with_stdin(&mut command, stdin, max_output)
+            .with_context(|| format!("While running `{} exec`", self.command))
+    }
+}
+
+// `&'static` so it can refill itself in the background
+impl Sandbox for &'static Pool {
+    fn run(&self, job: &Job) -> anyhow::Result<Output> {
+        let image = job.lang.image_name.as_str();
+        if !self.images.iter().any(|x| x == image) {
+            return Container::new(self.command.clone()).run(job);
+        }
+
+        let warm = self.checkout(image)?;
+        let out = self.run_in(&warm, job);
+        self.recycle(image, warm);
+        out
+    }
+}
+
+fn copy_files(job: &Job, to: &Path) -> anyhow::Result<()> {
+    for file in &job.files {
+        let dest = to.join(file);
+        let parent = dest.parent().unwrap();
+        fs::create_dir_all(parent).context("While creating job dir")?;
+        fs::set_permissions(parent, fs::Permissions::from_mode(0o777))?;
+        fs::copy(job.dir.join(file), &dest).context("While copying job files")?;
+    }
+    Ok(())
+}
+
+/// Delete what's left in a job dir from the host side (the container can't
+/// always delete files created by us)
+fn clear_dir(dir: &Path) -> anyhow::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        match path.is_dir() {
+            true => fs::remove_dir_all(&path)?,
+            false => fs::remove_file(&path)?,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use amplitude_common::{config::PoolConfig, config_and_set_path};
+
+    use super::{clear_dir, Job, Pool, Warm};
+    use crate::sandbox::container_command;
+
+    #[test]
+    fn test_recycle() {
+        let config = config_and_set_path().unwrap();
+        let Some(command) = container_command(&config.docker) else {
+            return;
+        };
+        let lang = &config.docker.language_config["python"];
+        let cfg = PoolConfig {
+            size: 1,
+            max_uses: 2,
+            languages: vec!["python".to_string()],
+        };
+        let pool = Pool::new(&config.docker, command, cfg);
+
+        let run = |warm: &Warm, code: &str| {
+            let dir = tempfile::tempdir().unwrap();
+            fs::write(dir.path().join("main.py"), code).unwrap();
+            let job = Job {
+                lang,
+                dir: dir.path(),
+                files: vec!["main.py"],
+                args: "",
+                stdin: &[],
+                timeout: 0,
+                max_output: 4096,
+            };
+            let out = pool.run_in(warm, &job).unwrap();
+            String::from_utf8(out.stdout).unwrap()
+        };
+
+        // everywhere a job could try to leave something for the next one,
+        // `run.sh` is copied into the next job and would print `left`
+        let paths = r#"["/runner/run.sh", "/runner/left", os.path.expanduser("~/left"), "/tmp/left", "/job/left"]"#;
+        let leave = format!(
+            "import os\n\
+            for path in {paths}:\n    \
+                try:\n        \
+                    open(path, 'a').write('echo left\\n')\n    \
+                except OSError:\n        \
+                    pass\n"
+        );
+        let find = format!("import os\nprint([x for x in {paths}[1:] if os.path.exists(x)])\n");
+
+        let image = &lang.image_name;
+        let warm = pool.start_container(image).unwrap();
+        run(&warm, &leave);
+        pool.recycle(image, warm);
+
+        let warm = pool.idle.lock().unwrap().get_mut(image).unwrap().warm.pop();
+        let warm = warm.expect("Container wasn't put back in the pool");
+        let out = run(&warm, &find);
+        pool.remove(&warm.id);
+        assert_eq!(out, "[]\n");
+    }
+
+    #[test]
+    fn test_clear_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("src/nested")).unwrap();
+        fs::write(dir.path().join("src/nested/main.rs"), "").unwrap();
+        fs::write(dir.path().join(".hidden"), "").unwrap();
+
+        clear_dir(dir.path()).unwrap();
+        assert!(dir.path().exists());
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+}
@@ -0,0 +1,111 @@
+//! Quick checks that a language works end to end: a plain program, and a
+//! function called by the language's runner template.
+
+use std::collections::HashMap;
+
+use amplitude_common::config::Config;
+use anyhow::Context;
+use serde_json::json;
+
+use crate::{
+    exercise::{runner_template, Exercise, ExerciseConfig, LanguageInfo, TestCase},
+    lang::Language,
+    runner::{run, ExitStatus},
+};
+
+/// Run a program printing `hello world`
+pub fn hello_world(lang: &Language, cfg: &Config) -> anyhow::Result<()> {
+    let lang_cfg = cfg
+        .docker
+        .language_config
+        .get(lang.image())
+        .context("Language not configured")?;
+    let out = run(
+        lang_cfg,
+        &cfg.docker,
+        hello_world_src(lang),
+        HashMap::new(),
+        "",
+    )?;
+    anyhow::ensure!(
+        out.status == ExitStatus::Success && out.stdout.trim() == "hello world",
+        "Expected `hello world`, got {:?} with {:?}\nstderr:\n{}",
+        out.stdout,
+        out.status,
+        out.stderr
+    );
+    Ok(())
+}
+
+/// Run the tests of an exercise with a single `add(int, int) -> int` function
+/// against a correct solution
+pub fn harness(lang: &Language, cfg: &Config) -> anyhow::Result<()> {
+    let mut config: ExerciseConfig = toml::from_str(
+        r#"
+        title = "add"
+        [functions.add]
+        inputs = ["int", "int"]
+        output = "int"
+        visible_cases = 2
+        hidden_cases = 1
+        "#,
+    )?;
+    config.functions.get_mut("add").unwrap().tests = [(1, 2), (-5, 3), (1_i64 << 40, 1)]
+        .into_iter()
+        .enumerate()
+        .map(|(i, (a, b))| TestCase {
+            inputs: vec![json!(a), json!(b)],
+            output: json!(a + b),
+            hidden: i == 2,
+        })
+        .collect();
+
+    let runner = runner_template(lang, &config, "add").context("While rendering runner")?;
+    let exercise = Exercise::new(
+        config,
+        HashMap::from([(
+            *lang,
+            LanguageInfo {
+                code: String::new(),
+                runner,
+            },
+        )]),
+        None,
+    );
+
+    let results = exercise.run_tests(lang, add_src(lang), "add", cfg)?;
+    let results = &results["add"];
+    anyhow::ensure!(results.passed, "Tests failed: {results:?}");
+    Ok(())
+}
+
+fn hello_world_src(lang: &Language) -> &'static str {
+    match lang {
+        Language::C => "#include <stdio.h>\nint main() {\n    puts(\"hello world\");\n}\n",
+        Language::Cpp => {
+            "#include <iostream>\nint main() {\n    std::cout << \"hello world\\n\";\n}\n"
+        }
+        Language::JavaScript => "console.log('hello world');\n",
+        Language::Python => "print('hello world')\n",
+        Language::Rust => "fn main() {\n    println!(\"hello world\");\n}\n",
+        Language::Java => {
+            "public class Main {\n    public static void main(String[] args) {\n        \
+             System.out.println(\"hello world\");\n    }\n}\n"
+        }
+    }
+}
+
+fn add_src(lang: &Language) -> &'static str {
+    match lang {
+        Language::C | Language::Cpp => {
+            "long long add(long long a, long long b) {\n    return a + b;\n}\n"
+        }
+        Language::JavaScript => "function add(a, b) {\n    return a + b;\n}\n",
+        Language::Python => "def add(a, b):\n    return a + b\n",
+        Language::Rust => "fn add(a: i64, b: i64) -> i64 {\n    a + b\n}\n",
+        Language::Java => {
+            "public class Solution {\n    public static long add(long a, long b) {\n        \
+             return a + b;\n    }\n}\n"
+        }
+    }
+}
@@ -0,0 +1,240 @@
+//! Exercises where the code is a whole program, that reads its input from
+//! stdin and prints its output to stdout, instead of functions called by a
+//! harness.
+
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    thread,
+};
+
+use amplitude_common::config::Config;
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    exercise::{CaseOutcome, TestResult, TestResults},
+    lang::Language,
+    runner::{run_with_stdin, RunOutput},
+};
+
+/// The `[io]` section of an exercise's config
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct IoConfig {
+    /// Arguments passed to the program
+    #[serde(default)]
+    pub args: String,
+    /// How the program's stdout is compared to the expected stdout
+    #[serde(default)]
+    pub compare: IoComparator,
+    pub tests: Vec<IoTestCase>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct IoTestCase {
+    pub stdin: String,
+    pub stdout: String,
+    #[serde(default)]
+    pub hidden: bool,
+    /// How much the test case counts towards the score
+    #[serde(default = "weight_default")]
+    pub weight: f64,
+}
+
+const fn weight_default() -> f64 {
+    1.0
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum IoComparator {
+    /// The output has to be the same byte for byte
+    Exact,
+    /// Line endings, whitespace at the end of lines and empty lines at the end
+    /// of the output are ignored
+    #[default]
+    Lines,
+    /// Only the words have to match, any run of whitespace is treated the same
+    Whitespace,
+}
+
+impl IoComparator {
+    pub fn matches(self, expected: &str, actual: &str) -> bool {
+        match self {
+            IoComparator::Exact => expected == actual,
+            IoComparator::Lines => lines(expected).eq(lines(actual)),
+            IoComparator::Whitespace => expected.split_whitespace().eq(actual.split_whitespace()),
+        }
+    }
+}
+
+/// The lines of `s` without trailing whitespace, or empty lines at the end
+fn lines(s: &str) -> impl Iterator<Item = &str> {
+    // `lines` also removes the `\r` of `\r\n`
+    s.trim_end().lines().map(str::trim_end)
+}
+
+impl IoConfig {
+    /// Run the program once for every test case, `io_runs` at a time. The
+    /// results of the visible test cases are in the order they are in the
+    /// config.
+    pub fn run_tests(
+        &self,
+        lang: &Language,
+        content: &str,
+        fixtures: &HashMap<String, &[u8]>,
+        cfg: &Config,
+    ) -> anyhow::Result<TestResults> {
+        let lang_cfg = cfg.docker.language_config.get(lang.image()).unwrap();
+        let next = AtomicUsize::new(0);
+        let outputs = Mutex::new(Vec::from_iter(self.tests.iter().map(|_| None)));
+        thread::scope(|s| {
+            for _ in 0..cfg.docker.io_runs.clamp(1, self.tests.len().max(1)) {
+                s.spawn(|| loop {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(test) = self.tests.get(i) else {
+                        break;
+                    };
+                    let output = run_with_stdin(
+                        lang_cfg,
+                        &cfg.docker,
+                        content,
+                        fixtures.clone(),
+                        &self.args,
+                        test.stdin.as_bytes(),
+                    );
+                    outputs.lock().unwrap()[i] = Some(output);
+                });
+            }
+        });
+        let outputs = outputs
+            .into_inner()
+            .unwrap()
+            .into_iter()
+            .map(|x| x.unwrap())
+            .collect::<anyhow::Result<Vec<_>>>()
+            .context("While running program")?;
+
+        let mut results = Vec::new();
+        let mut cases = Vec::new();
+        for (test, output) in self.tests.iter().zip(outputs) {
+            let result = self.verdict(test, output);
+            cases.push(CaseOutcome {
+                hidden: test.hidden,
+                passed: matches!(result, TestResult::Correct { .. }),
+                weight: test.weight,
+            });
+            if !test.hidden {
+                results.push(result);
+            }
+        }
+
+        // there is only one program, so the weight doesn't matter
+        Ok(TestResults::new(results, &cases, 1.0))
+    }
+
+    fn verdict(&self, test: &IoTestCase, output: RunOutput) -> TestResult {
+        if let Some(verdict) = TestResult::from_run_output(&output) {
+            return verdict;
+        }
+
+        let stdout = output.stdout;
+        match self.compare.matches(&test.stdout, &stdout) {
+            true => TestResult::Correct {
+                stdout,
+                message: None,
+            },
+            false => TestResult::Incorrect {
+                output: Value::String(stdout.clone()),
+                stdout,
+                message: None,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use amplitude_common::config_and_set_path;
+
+    use crate::exercise::HiddenResults;
+
+    use super::*;
+
+    #[test]
+    fn test_compare() {
+        let lines = IoComparator::Lines;
+        assert!(lines.matches("1\n2\n", "1  \r\n2\r\n\n"));
+        assert!(lines.matches("", "\n"));
+        assert!(!lines.matches("1 2\n", "1  2\n"));
+        assert!(IoComparator::Whitespace.matches("1 2\n", "1\n  2"));
+        assert!(!IoComparator::Exact.matches("1\n", "1\r\n"));
+    }
+
+    #[test]
+    fn test_io() {
+        let cfg = config_and_set_path().unwrap();
+        let io = IoConfig {
+            args: "10".to_string(),
+            compare: IoComparator::Lines,
+            tests: vec![
+                IoTestCase {
+                    stdin: "1 2\n".to_string(),
+                    stdout: "13\n".to_string(),
+                    hidden: false,
+                    weight: 1.0,
+                },
+                IoTestCase {
+                    stdin: "5 5\n".to_string(),
+                    stdout: "0\n".to_string(),
+                    hidden: false,
+                    weight: 1.0,
+                },
+                IoTestCase {
+                    stdin: "0 0".to_string(),
+                    stdout: "10".to_string(),
+                    hidden: true,
+                    weight: 2.0,
+                },
+            ],
+        };
+
+        // prints the sum of the numbers on stdin, the first argument and the
+        // number in a fixture
+        let code = "import sys\nextra = int(open('data/extra.txt').read())\n\
+                    print(sum(map(int, input().split())) + int(sys.argv[1]) + extra)\n";
+        let fixtures = HashMap::from([("data/extra.txt".to_string(), "0".as_bytes())]);
+        let results = io
+            .run_tests(&Language::Python, code, &fixtures, &cfg)
+            .unwrap();
+        assert_eq!(
+            results,
+            TestResults {
+                results: vec![
+                    TestResult::Correct {
+                        stdout: "13\n".to_string(),
+                        message: None,
+                    },
+                    TestResult::Incorrect {
+                        stdout: "20\n".to_string(),
+                        output: Value::String("20\n".to_string()),
+                        message: None,
+                    },
+                ],
+                hidden: HiddenResults {
+                    passed: 1,
+                    total: 1,
+                },
+                passed: false,
+                score: 0.75,
+                weight: 1.0,
+            }
+        );
+    }
+}
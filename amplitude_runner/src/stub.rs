@@ -0,0 +1,347 @@
+//! Starter code generated from the signatures of an exercise's functions, and
+//! checks that hand-written starter code declares the same signatures.
+
+use crate::{
+    codegen::codegen,
+    exercise::{ExerciseConfig, FunctionConfig},
+    lang::Language,
+};
+
+/// Starter code for the functions of an exercise, with each function
+/// declared with a placeholder body
+pub fn generate_stub(lang: &Language, cfg: &ExerciseConfig) -> anyhow::Result<String> {
+    codegen(lang).stub(&sorted_functions(cfg))
+}
+
+/// Make sure hand-written starter code declares every function of the
+/// exercise with the right signature. The error lists every mismatch.
+pub fn check_stub(lang: &Language, cfg: &ExerciseConfig, code: &str) -> anyhow::Result<()> {
+    let mut mismatches = Vec::new();
+    for (name, func) in sorted_functions(cfg) {
+        let Some(found) = find_signature(lang, code, name) else {
+            mismatches.push(format!("`{name}` isn't declared"));
+            continue;
+        };
+        mismatches.extend(signature_mismatches(lang, name, func, &found)?);
+    }
+
+    anyhow::ensure!(
+        mismatches.is_empty(),
+        "The starter code doesn't match the config:\n{}",
+        mismatches.join("\n")
+    );
+    Ok(())
+}
+
+/// sorted so the output is the same every time
+fn sorted_functions(cfg: &ExerciseConfig) -> Vec<(&String, &FunctionConfig)> {
+    let mut functions = cfg.functions.iter().collect::<Vec<_>>();
+    functions.sort_by_key(|(name, _)| *name);
+    functions
+}
+
+/// A function declaration, with the types as they are written. Types are
+/// `None` where the language doesn't have them (or doesn't require them).
+#[derive(Debug, PartialEq, Eq)]
+struct Signature {
+    params: Vec<Option<String>>,
+    output: Option<String>,
+}
+
+fn signature_mismatches(
+    lang: &Language,
+    name: &str,
+    func: &FunctionConfig,
+    found: &Signature,
+) -> anyhow::Result<Vec<String>> {
+    let gen = codegen(lang);
+    let mut mismatches = Vec::new();
+
+    if found.params.len() != func.inputs.len() {
+        mismatches.push(format!(
+            "`{name}` takes {} parameters, expected {}",
+            found.params.len(),
+            func.inputs.len()
+        ));
+    } else {
+        for (i, (param, ty)) in found.params.iter().zip(&func.inputs).enumerate() {
+            let expected = gen.type_name(ty)?;
+            if let Some(param) = param.as_deref() {
+                if normalize_type(lang, param) != normalize_type(lang, &expected) {
+                    mismatches.push(format!(
+                        "Parameter {} of `{name}` is `{param}`, expected `{expected}`",
+                        i + 1
+                    ));
+                }
+            }
+        }
+    }
+
+    let expected = gen.type_name(&func.output)?;
+    if let Some(output) = found.output.as_deref() {
+        if normalize_type(lang, output) != normalize_type(lang, &expected) {
+            mismatches.push(format!(
+                "`{name}` returns `{output}`, expected `{expected}`"
+            ));
+        }
+    }
+    Ok(mismatches)
+}
+
+/// Write a type the same way no matter how it's spaced, or whether it's
+/// passed by (const) reference. Namespaces the solution could import are
+/// removed.
+fn normalize_type(lang: &Language, ty: &str) -> String {
+    let ty = ty.trim();
+    let ty = ty.strip_prefix("const ").unwrap_or(ty);
+    let ty = ty.strip_prefix("final ").unwrap_or(ty);
+    let ty = ty.strip_suffix('&').unwrap_or(ty);
+
+    let ty = match lang {
+        Language::Cpp => ty.replace("std::", ""),
+        Language::Java => ty.replace("java.util.", ""),
+        Language::Rust => ty.replace("std::collections::", ""),
+        _ => ty.to_string(),
+    };
+
+    // only keep the spaces between two words, like in `long long`
+    let words = ty.split_whitespace().collect::<Vec<_>>();
+    let mut out = String::new();
+    for (i, word) in words.iter().enumerate() {
+        let is_ident = |c: Option<char>| c.is_some_and(|c| c.is_ascii_alphanumeric() || c == '_');
+        if i > 0 && is_ident(out.chars().last()) && is_ident(word.chars().next()) {
+            out.push(' ');
+        }
+        out.push_str(word);
+    }
+    out
+}
+
+/// Keywords that can come before the return type in C, C++ and Java
+const MODIFIERS: &[&str] = &[
+    "public",
+    "private",
+    "protected",
+    "static",
+    "inline",
+    "extern",
+];
+
+/// The signature of the function `name` declared in `code`
+fn find_signature(lang: &Language, code: &str, name: &str) -> Option<Signature> {
+    let keyword = match lang {
+        Language::Python => Some("def"),
+        Language::JavaScript => Some("function"),
+        Language::Rust => Some("fn"),
+        Language::C | Language::Cpp | Language::Java => None,
+    };
+
+    for start in find_word(code, name) {
+        let before = &code[..start];
+        let line = &before[before.rfind('\n').map_or(0, |x| x + 1)..];
+        let Some((params, after)) = parenthesized(&code[start + name.len()..]) else {
+            continue;
+        };
+        let params = split_params(params);
+
+        match keyword {
+            Some(keyword) => {
+                if line.split_whitespace().last() != Some(keyword) {
+                    continue;
+                }
+                return Some(match lang {
+                    Language::Rust => Signature {
+                        params: params
+                            .iter()
+                            .map(|param| param.split_once(':').map(|(_, ty)| ty.trim().to_string()))
+                            .collect(),
+                        output: Some(rust_output(after)),
+                    },
+                    _ => Signature {
+                        params: params.iter().map(|_| None).collect(),
+                        output: None,
+                    },
+                });
+            }
+            None => {
+                // the return type is everything before the name on its line,
+                // without modifiers. Calls have nothing before the name, or
+                // something that isn't a type, like `return` or `=`.
+                let output = line
+                    .split_whitespace()
+                    .skip_while(|x| MODIFIERS.contains(x))
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                let is_type = !output.is_empty()
+                    && output != "return"
+                    && !output.contains(|c: char| "=;(){}+-/".contains(c));
+                if !is_type || !after.trim_start().starts_with('{') {
+                    continue;
+                }
+                return Some(Signature {
+                    params: params
+                        .iter()
+                        .filter(|x| x.as_str() != "void")
+                        .map(|param| Some(c_param_type(param)))
+                        .collect(),
+                    output: Some(output),
+                });
+            }
+        }
+    }
+    None
+}
+
+/// Where `word` appears in `code` as a whole word
+fn find_word<'a>(code: &'a str, word: &'a str) -> impl Iterator<Item = usize> + 'a {
+    let is_ident = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    code.match_indices(word).map(|(i, _)| i).filter(move |&i| {
+        !code[..i].ends_with(is_ident) && !code[i + word.len()..].starts_with(is_ident)
+    })
+}
+
+/// If `s` starts with `(`, what's inside the parentheses and what comes after
+/// them
+fn parenthesized(s: &str) -> Option<(&str, &str)> {
+    let s = s.trim_start().strip_prefix('(')?;
+    let mut depth = 0_usize;
+    for (i, c) in s.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' if depth == 0 => return Some((&s[..i], &s[i + 1..])),
+            ')' => depth -= 1,
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Split parameters on the commas that aren't inside any brackets
+fn split_params(s: &str) -> Vec<String> {
+    let mut out = Vec::new();
+    let mut depth = 0_usize;
+    let mut current = String::new();
+    for c in s.chars() {
+        match c {
+            '<' | '(' | '[' | '{' => depth += 1,
+            '>' | ')' | ']' | '}' => depth = depth.saturating_sub(1),
+            ',' if depth == 0 => {
+                out.push(current.trim().to_string());
+                current.clear();
+                continue;
+            }
+            _ => {}
+        }
+        current.push(c);
+    }
+    if !current.trim().is_empty() {
+        out.push(current.trim().to_string());
+    }
+    out
+}
+
+/// The type of a C, C++ or Java parameter, which is everything but the name at
+/// the end
+fn c_param_type(param: &str) -> String {
+    let name_len = param
+        .chars()
+        .rev()
+        .take_while(|c| c.is_ascii_alphanumeric() || *c == '_')
+        .count();
+    param[..param.len() - name_len].trim().to_string()
+}
+
+/// The return type of a rust function, from what comes after its parameters
+fn rust_output(after: &str) -> String {
+    let end = after.find(['{', ';']).unwrap_or(after.len());
+    let after = after[..end].split(" where ").next().unwrap().trim();
+    match after.strip_prefix("->") {
+        Some(output) => output.trim().to_string(),
+        None => "()".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn config() -> ExerciseConfig {
+        toml::from_str(
+            r#"
+            title = "test"
+            [types]
+            Point = "{x: int, y: int}"
+            [functions.add]
+            inputs = ["int", "int[]"]
+            output = "int"
+            [functions.closest]
+            inputs = ["Point[]", "map<string, int>"]
+            output = "Point?"
+            "#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_generated_stubs_match() {
+        let cfg = config();
+        for lang in enum_iterator::all::<Language>() {
+            let stub = generate_stub(&lang, &cfg).unwrap();
+            check_stub(&lang, &cfg, &stub).unwrap_or_else(|e| panic!("{lang:?}: {e}\n{stub}"));
+        }
+
+        assert_eq!(
+            generate_stub(&Language::Rust, &cfg).unwrap(),
+            "fn add(arg1: i64, arg2: Vec<i64>) -> i64 {\n    todo!()\n}\n\n\
+             fn closest(arg1: Vec<Point>, arg2: std::collections::HashMap<String, i64>) \
+             -> Option<Point> {\n    todo!()\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_check_stub() {
+        let cfg = config();
+        let check = |lang, code| check_stub(&lang, &cfg, code).map_err(|e| e.to_string());
+
+        assert!(check(
+            Language::Cpp,
+            "#include <vector>\n\
+             long long add(long long a, const std::vector<long long> &b) {\n    return a;\n}\n\
+             std::optional<Point> closest(const vector<Point>& points, std::map<std::string, long long> m) {\n    \
+             // add(1, 2) isn't a declaration\n    return {};\n}\n"
+        )
+        .is_ok());
+        assert!(check(
+            Language::Java,
+            "import java.util.*;\n\npublic class Solution {\n    \
+             public static long add(long a, long[] b) { return a; }\n    \
+             static Point closest(Point[] p, HashMap<String, Long> m) { return null; }\n}\n"
+        )
+        .is_ok());
+        assert!(check(
+            Language::Python,
+            "def add(a, b):\n    return a\n\ndef closest(points, m=None):\n    pass\n"
+        )
+        .is_ok());
+
+        assert_eq!(
+            check(
+                Language::Rust,
+                "fn add(a: i64, b: &[i64]) -> i32 { 0 }\nfn other() {}\n"
+            )
+            .unwrap_err(),
+            "The starter code doesn't match the config:\n\
+             Parameter 2 of `add` is `&[i64]`, expected `Vec<i64>`\n\
+             `add` returns `i32`, expected `i64`\n\
+             `closest` isn't declared"
+        );
+        assert_eq!(
+            check(
+                Language::JavaScript,
+                "function add(a) {}\nfunction closest(a, b) {}\n"
+            )
+            .unwrap_err(),
+            "The starter code doesn't match the config:\n`add` takes 1 parameters, expected 2"
+        );
+    }
+}
@@ -1,6 +1,13 @@
-use std::{collections::HashMap, fmt};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+};
 
+use anyhow::Context;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::codegen::struct_fields;
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(untagged)]
@@ -9,10 +16,34 @@ pub enum VariableType {
     Int,
     Float,
     String,
+    /// A single unicode character, written as a one character string in json
+    Char,
     Boolean,
     Array(Box<VariableType>),
     Struct(HashMap<String, VariableType>),
     Tuple(Vec<VariableType>),
+    /// `T?`, `null` in json when there is no value
+    Optional(Box<VariableType>),
+    /// `map<K, V>`, written as an array of `[key, value]` pairs in json. Keys
+    /// can only be primitives, see [`VariableType::is_map_key`].
+    Map(Box<VariableType>, Box<VariableType>),
+    /// A struct declared in the `[types]` table of an exercise, written as its
+    /// name. Serialized as a json object, like [`VariableType::Struct`].
+    Named(Box<DynStruct>),
+}
+
+/// A struct with a name, see [`VariableType::Named`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DynStruct {
+    pub name: String,
+    /// In the order they are declared
+    pub fields: Vec<Field>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Field {
+    pub name: String,
+    pub ty: VariableType,
 }
 
 impl From<VariableType> for String {
@@ -27,8 +58,12 @@ impl<'a> From<&'a VariableType> for String {
             VariableType::Int => "int".to_string(),
             VariableType::Float => "float".to_string(),
             VariableType::String => "string".to_string(),
+            VariableType::Char => "char".to_string(),
             VariableType::Boolean => "bool".to_string(),
             VariableType::Array(ty) => format!("{}[]", ty),
+            VariableType::Optional(ty) => format!("{}?", ty),
+            VariableType::Map(key, value) => format!("map<{}, {}>", key, value),
+            VariableType::Named(named) => named.name.clone(),
             VariableType::Struct(fields) => {
                 let mut out = String::new();
                 for (name, ty) in fields {
@@ -57,75 +92,428 @@ impl fmt::Display for VariableType {
 impl<'a> TryFrom<&'a str> for VariableType {
     type Error = anyhow::Error;
 
+    /// Parse a type. Named types are left without fields, they're filled in
+    /// by [`VariableType::resolve`].
     fn try_from(value: &'a str) -> Result<Self, Self::Error> {
-        let s = value.trim();
+        let mut parser = Parser::new(value);
+        let ty = parser.parse_type()?;
+        parser.end()?;
+        Ok(ty)
+    }
+}
+
+/// A recursive descent parser for types
+struct Parser<'a> {
+    src: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(src: &'a str) -> Self {
+        Self { src, pos: 0 }
+    }
+
+    /// An error pointing at byte `pos` of the source
+    fn error_at(&self, pos: usize, message: impl fmt::Display) -> anyhow::Error {
+        let column = self.src[..pos].chars().count() + 1;
+        anyhow::anyhow!("{message} at column {column} of `{}`", self.src)
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = &self.src[self.pos..];
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.skip_whitespace();
+        self.src[self.pos..].chars().next()
+    }
 
-        // array
-        if let Some(s) = s.strip_suffix("[]") {
-            Ok(VariableType::Array(Box::new(VariableType::try_from(s)?)))
+    /// Consume `c` if it's the next character
+    fn eat(&mut self, c: char) -> bool {
+        let found = self.peek() == Some(c);
+        if found {
+            self.pos += c.len_utf8();
         }
-        // struct
-        else if s.starts_with('{') {
-            anyhow::ensure!(
-                s.ends_with('}'),
-                "Expected ending `}}` when starting with `{{`"
-            );
-            let s = &s[1..s.len() - 1];
-            let mut map = HashMap::new();
-            let mut iter = s.split(',').peekable();
-
-            while let Some(field) = iter.next() {
-                let field = field.split_once(':');
-                if field.is_none() {
-                    if iter.peek().is_some() {
-                        anyhow::bail!("Expected `:` in struct field");
-                    } else {
+        found
+    }
+
+    fn expect(&mut self, c: char) -> anyhow::Result<()> {
+        match self.eat(c) {
+            true => Ok(()),
+            false => Err(self.unexpected(&format!("`{c}`"))),
+        }
+    }
+
+    fn unexpected(&mut self, expected: &str) -> anyhow::Error {
+        let message = match self.peek() {
+            Some(c) => format!("Expected {expected}, found `{c}`"),
+            None => format!("Expected {expected}, found the end"),
+        };
+        self.error_at(self.pos, message)
+    }
+
+    /// Make sure there's nothing left to parse
+    fn end(&mut self) -> anyhow::Result<()> {
+        match self.peek() {
+            Some(_) => Err(self.unexpected("the end")),
+            None => Ok(()),
+        }
+    }
+
+    /// A word made of alphanumeric characters, `_` and `-`
+    fn word(&mut self) -> Option<&'a str> {
+        self.skip_whitespace();
+        let rest = &self.src[self.pos..];
+        let len = rest
+            .find(|c: char| !c.is_ascii_alphanumeric() && !"_-".contains(c))
+            .unwrap_or(rest.len());
+        self.pos += len;
+        (len > 0).then_some(&rest[..len])
+    }
+
+    fn parse_type(&mut self) -> anyhow::Result<VariableType> {
+        let mut ty = self.primary()?;
+        loop {
+            let start = self.pos;
+            if self.eat('[') {
+                self.expect(']')?;
+                ty = VariableType::Array(Box::new(ty));
+            } else if self.eat('?') {
+                if matches!(ty, VariableType::Optional(_)) {
+                    return Err(self.error_at(
+                        start,
+                        format!("`{ty}?` is not allowed, optionals can't be nested directly"),
+                    ));
+                }
+                ty = VariableType::Optional(Box::new(ty));
+            } else {
+                return Ok(ty);
+            }
+        }
+    }
+
+    /// A type without any `[]` or `?` after it
+    fn primary(&mut self) -> anyhow::Result<VariableType> {
+        match self.peek() {
+            Some('{') => {
+                self.pos += 1;
+                let fields = self.fields()?;
+                Ok(VariableType::Struct(
+                    fields.into_iter().map(|x| (x.name, x.ty)).collect(),
+                ))
+            }
+            Some('(') => {
+                self.pos += 1;
+                let mut fields = Vec::new();
+                while !self.eat(')') {
+                    fields.push(self.parse_type()?);
+                    if !self.eat(',') {
+                        self.expect(')')?;
                         break;
                     }
                 }
+                Ok(VariableType::Tuple(fields))
+            }
+            _ => {
+                let start = self.pos;
+                let Some(word) = self.word() else {
+                    return Err(self.unexpected("a type"));
+                };
+                Ok(match word {
+                    "int" => VariableType::Int,
+                    "float" => VariableType::Float,
+                    "string" => VariableType::String,
+                    "char" => VariableType::Char,
+                    "bool" => VariableType::Boolean,
+                    "map" => {
+                        self.expect('<')?;
+                        let key_start = self.pos;
+                        let key = self.parse_type()?;
+                        if !key.is_map_key() {
+                            return Err(self.error_at(
+                                key_start,
+                                format!(
+                                    "Map keys have to be `int`, `string`, `char` or `bool`, not `{key}`"
+                                ),
+                            ));
+                        }
+                        self.expect(',')?;
+                        let value = self.parse_type()?;
+                        self.expect('>')?;
+                        VariableType::Map(Box::new(key), Box::new(value))
+                    }
+                    _ if word.starts_with(|c: char| c.is_ascii_uppercase()) => {
+                        VariableType::Named(Box::new(DynStruct {
+                            name: word.to_string(),
+                            fields: Vec::new(),
+                        }))
+                    }
+                    _ => return Err(self.error_at(start, format!("Unknown type `{word}`"))),
+                })
+            }
+        }
+    }
 
-                let (mut ident, ty) = field.unwrap();
-                ident = ident.trim();
-                let predicate = |c: char| !c.is_ascii_alphanumeric() && !"_-".contains(c);
-                anyhow::ensure!(
-                    !ident.contains(predicate),
-                    "Invalid character {}, Field names should only be made up of alphanumeric characters or \"_-\"",
-                    ident.as_bytes()[ident.find(predicate).unwrap()] as char
-                );
-                map.insert(ident.to_string(), VariableType::try_from(ty)?);
-            }
-            Ok(VariableType::Struct(map))
+    /// The fields of a struct, after its `{`, in the order they're written
+    fn fields(&mut self) -> anyhow::Result<Vec<Field>> {
+        let mut fields = Vec::<Field>::new();
+        while !self.eat('}') {
+            let start = self.pos;
+            let Some(name) = self.word() else {
+                return Err(self.unexpected("a field name"));
+            };
+            if fields.iter().any(|x| x.name == name) {
+                return Err(self.error_at(start, format!("Duplicate field `{name}`")));
+            }
+            self.expect(':')?;
+            fields.push(Field {
+                name: name.to_string(),
+                ty: self.parse_type()?,
+            });
+            if !self.eat(',') {
+                self.expect('}')?;
+                break;
+            }
         }
-        // tuple
-        else if s.starts_with('(') {
-            anyhow::ensure!(
-                s.ends_with(')'),
-                "Expected ending `)` when starting with `(`"
+        Ok(fields)
+    }
+}
+
+/// Names that can't be used for named types, because the harnesses or the
+/// languages already use them
+const RESERVED_NAMES: &[&str] = &[
+    "Boolean",
+    "Character",
+    "Context",
+    "Double",
+    "Long",
+    "Main",
+    "Map",
+    "None",
+    "Object",
+    "Option",
+    "Self",
+    "Solution",
+    "String",
+    "ToJson",
+    "Tuple",
+    "Vec",
+];
+
+/// Parse the `[types]` table of an exercise, where every entry is a struct
+/// like `Point = "{x: int, y: int}"`. Types can use the types declared before
+/// or after them, as long as no type contains itself.
+pub fn parse_type_table(
+    table: &HashMap<String, String>,
+) -> anyhow::Result<HashMap<String, VariableType>> {
+    let mut declared = HashMap::new();
+    for (name, src) in table {
+        let valid = name.starts_with(|c: char| c.is_ascii_uppercase())
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        anyhow::ensure!(
+            valid,
+            "Invalid type name `{name}`, type names start with an uppercase letter and only \
+             contain alphanumeric characters or `_`"
+        );
+        anyhow::ensure!(
+            !RESERVED_NAMES.contains(&name.as_str()),
+            "`{name}` can't be used as a type name"
+        );
+
+        let mut parser = Parser::new(src);
+        parser.expect('{')?;
+        let fields = parser
+            .fields()
+            .and_then(|fields| parser.end().map(|_| fields))
+            .with_context(|| format!("In type `{name}`"))?;
+        declared.insert(name.as_str(), fields);
+    }
+
+    // sorted so errors are the same every time
+    let mut names = declared.keys().copied().collect::<Vec<_>>();
+    names.sort();
+    let mut types = HashMap::new();
+    for name in names {
+        resolve_declared(name, &declared, &mut types, &mut Vec::new())?;
+    }
+    Ok(types)
+}
+
+/// Resolve the declared type `name` into `types`, after the types it uses.
+/// `stack` is the types being resolved, to find types that contain themselves.
+fn resolve_declared<'a>(
+    name: &'a str,
+    declared: &HashMap<&'a str, Vec<Field>>,
+    types: &mut HashMap<String, VariableType>,
+    stack: &mut Vec<&'a str>,
+) -> anyhow::Result<()> {
+    if types.contains_key(name) {
+        return Ok(());
+    }
+    stack.push(name);
+    let mut fields = declared[name].clone();
+
+    let mut used = Vec::new();
+    for field in &fields {
+        field.ty.named_types(&mut used);
+    }
+    for used in used {
+        let Some((used, _)) = declared.get_key_value(used.as_str()) else {
+            anyhow::bail!("Unknown type `{used}` in type `{name}`");
+        };
+        if stack.contains(used) {
+            stack.push(used);
+            anyhow::bail!(
+                "Type `{used}` contains itself ({}), which isn't supported",
+                stack.join(" -> ")
             );
-            let s = &s[1..s.len() - 1];
-            let mut vec = Vec::new();
-
-            let mut iter = s.split(',').peekable();
-            while let Some(ty) = iter.next() {
-                if ty.trim().is_empty() {
-                    if iter.peek().is_some() {
-                        anyhow::bail!("Found empty tuple field");
-                    } else {
-                        break;
-                    }
+        }
+        resolve_declared(used, declared, types, stack)?;
+    }
+
+    for field in &mut fields {
+        field.ty.resolve(types)?;
+    }
+    stack.pop();
+    types.insert(
+        name.to_string(),
+        VariableType::Named(Box::new(DynStruct {
+            name: name.to_string(),
+            fields,
+        })),
+    );
+    Ok(())
+}
+
+impl VariableType {
+    /// Replace the named types in this type with their definitions in `types`,
+    /// see [`parse_type_table`]
+    pub fn resolve(&mut self, types: &HashMap<String, VariableType>) -> anyhow::Result<()> {
+        match self {
+            VariableType::Named(named) => {
+                *self = types
+                    .get(&named.name)
+                    .with_context(|| format!("Unknown type `{}`", named.name))?
+                    .clone();
+            }
+            VariableType::Array(inner) | VariableType::Optional(inner) => inner.resolve(types)?,
+            VariableType::Map(key, value) => {
+                key.resolve(types)?;
+                value.resolve(types)?;
+            }
+            VariableType::Tuple(fields) => {
+                for ty in fields {
+                    ty.resolve(types)?;
                 }
-                vec.push(VariableType::try_from(ty)?);
             }
-            Ok(VariableType::Tuple(vec))
-        } else {
-            match s {
-                "int" => Ok(VariableType::Int),
-                "float" => Ok(VariableType::Float),
-                "string" => Ok(VariableType::String),
-                "bool" => Ok(VariableType::Boolean),
-                _ => anyhow::bail!("Could not interpret type"),
+            VariableType::Struct(fields) => {
+                for ty in fields.values_mut() {
+                    ty.resolve(types)?;
+                }
             }
+            _ => {}
         }
+        Ok(())
+    }
+
+    /// Add the names of the named types used directly by this type to `out`
+    fn named_types(&self, out: &mut Vec<String>) {
+        match self {
+            VariableType::Named(named) => out.push(named.name.clone()),
+            VariableType::Array(inner) | VariableType::Optional(inner) => inner.named_types(out),
+            VariableType::Map(key, value) => {
+                key.named_types(out);
+                value.named_types(out);
+            }
+            VariableType::Tuple(fields) => fields.iter().for_each(|ty| ty.named_types(out)),
+            VariableType::Struct(fields) => fields.values().for_each(|ty| ty.named_types(out)),
+            _ => {}
+        }
+    }
+
+    /// Whether this type can be used as the key of a map
+    pub fn is_map_key(&self) -> bool {
+        matches!(
+            self,
+            VariableType::Int | VariableType::String | VariableType::Char | VariableType::Boolean
+        )
+    }
+
+    /// Make sure `value` is a valid json value of this type
+    pub fn check_value(&self, value: &Value) -> anyhow::Result<()> {
+        let valid = match self {
+            VariableType::Int => value.is_i64(),
+            // non-finite floats are serialized as `null`
+            VariableType::Float => value.is_number() || value.is_null(),
+            VariableType::String => value.is_string(),
+            VariableType::Char => value.as_str().is_some_and(|s| s.chars().count() == 1),
+            VariableType::Boolean => value.is_boolean(),
+            VariableType::Optional(inner) => {
+                return match value.is_null() {
+                    true => Ok(()),
+                    false => inner.check_value(value),
+                }
+            }
+            VariableType::Array(inner) => {
+                let array = value.as_array().with_context(|| self.mismatch(value))?;
+                for (i, item) in array.iter().enumerate() {
+                    inner
+                        .check_value(item)
+                        .with_context(|| format!("In item {i} of `{value}`"))?;
+                }
+                return Ok(());
+            }
+            VariableType::Tuple(fields) => {
+                let array = value
+                    .as_array()
+                    .filter(|x| x.len() == fields.len())
+                    .with_context(|| self.mismatch(value))?;
+                for (i, (ty, item)) in fields.iter().zip(array).enumerate() {
+                    ty.check_value(item)
+                        .with_context(|| format!("In item {i} of `{value}`"))?;
+                }
+                return Ok(());
+            }
+            VariableType::Struct(_) | VariableType::Named(_) => {
+                let fields = struct_fields(self);
+                let object = value
+                    .as_object()
+                    .filter(|x| x.len() == fields.len())
+                    .with_context(|| self.mismatch(value))?;
+                for (name, ty) in fields {
+                    let field = object.get(name).with_context(|| self.mismatch(value))?;
+                    ty.check_value(field)
+                        .with_context(|| format!("In field `{name}` of `{value}`"))?;
+                }
+                return Ok(());
+            }
+            VariableType::Map(key, val) => {
+                let pairs = value.as_array().with_context(|| self.mismatch(value))?;
+                let mut keys = HashSet::new();
+                for pair in pairs {
+                    let [k, v] = pair.as_array().map(|x| &x[..]).unwrap_or_default() else {
+                        return Err(self.mismatch(value));
+                    };
+                    key.check_value(k)
+                        .and_then(|_| val.check_value(v))
+                        .with_context(|| format!("In entry `{pair}` of `{value}`"))?;
+                    anyhow::ensure!(
+                        keys.insert(k.to_string()),
+                        "Duplicate key `{k}` in `{value}`"
+                    );
+                }
+                return Ok(());
+            }
+        };
+        match valid {
+            true => Ok(()),
+            false => Err(self.mismatch(value)),
+        }
+    }
+
+    fn mismatch(&self, value: &Value) -> anyhow::Error {
+        anyhow::anyhow!("Expected a value of type `{self}`, found `{value}`")
     }
 }
 
@@ -139,6 +527,8 @@ impl TryFrom<String> for VariableType {
 
 #[cfg(test)]
 mod test {
+    use serde_json::json;
+
     use super::*;
 
     #[test]
@@ -199,6 +589,130 @@ mod test {
         test("(int, string,)", tuple(&[int(), string()]));
         test("(int, string)", tuple(&[int(), string()]));
 
+        let optional = |t| VariableType::Optional(Box::new(t));
+        let map = |k, v| VariableType::Map(Box::new(k), Box::new(v));
+        test("char", VariableType::Char);
+        test("int?", optional(int()));
+        test("int?[]", array(optional(int())));
+        test("int[]?", optional(array(int())));
+        test("map<string, int>", map(string(), int()));
+        test(
+            "map<char, map<int, (int, bool)>>",
+            map(VariableType::Char, map(int(), tuple(&[int(), bool()]))),
+        );
+        test(
+            "(map<int, int>, {a: map<int, string?>})",
+            tuple(&[
+                map(int(), int()),
+                class(&[("a", map(int(), optional(string())))]),
+            ]),
+        );
+        assert!(VariableType::try_from("int??").is_err());
+        assert!(VariableType::try_from("map<int[], int>").is_err());
+        assert!(VariableType::try_from("map<int>").is_err());
+
+        // nested types in structs
+        test(
+            "{a: (int, int), b: map<int, int>}",
+            class(&[("a", tuple(&[int(), int()])), ("b", map(int(), int()))]),
+        );
+        let error = |s: &str| VariableType::try_from(s).unwrap_err().to_string();
+        assert_eq!(
+            error("map<int int>"),
+            "Expected `,`, found `i` at column 9 of `map<int int>`"
+        );
+        assert_eq!(
+            error("{a: int"),
+            "Expected `}`, found the end at column 8 of `{a: int`"
+        );
+        assert_eq!(
+            error("(int, integer)"),
+            "Unknown type `integer` at column 7 of `(int, integer)`"
+        );
+        assert_eq!(
+            error("{a: int, a: int}"),
+            "Duplicate field `a` at column 10 of `{a: int, a: int}`"
+        );
+        assert!(VariableType::try_from("int[] int").is_err());
+
+        // types are written the same way they are parsed
+        let ty = VariableType::try_from("map<string, int?[]>").unwrap();
+        assert_eq!(VariableType::try_from(ty.to_string()).unwrap(), ty);
+
         Ok(())
     }
+
+    #[test]
+    fn test_type_table() {
+        let table = |types: &[(&str, &str)]| {
+            parse_type_table(
+                &types
+                    .iter()
+                    .map(|(a, b)| (a.to_string(), b.to_string()))
+                    .collect(),
+            )
+        };
+
+        let types = table(&[
+            ("Segment", "{a: Point, b: Point}"),
+            ("Point", "{y: int, x: int}"),
+        ])
+        .unwrap();
+        let VariableType::Named(segment) = &types["Segment"] else {
+            panic!("Expected a named type");
+        };
+        assert_eq!(segment.fields[1].ty, types["Point"]);
+        let VariableType::Named(point) = &types["Point"] else {
+            panic!("Expected a named type");
+        };
+        // fields stay in the order they are declared
+        assert_eq!(point.fields[0].name, "y");
+
+        let mut ty = VariableType::try_from("map<int, Segment[]>").unwrap();
+        ty.resolve(&types).unwrap();
+        assert_eq!(ty.to_string(), "map<int, Segment[]>");
+        assert!(ty
+            .check_value(&json!([[1, [{"a": {"x": 1, "y": 2}, "b": {"x": 3, "y": 4}}]]]))
+            .is_ok());
+        assert!(ty.check_value(&json!([[1, [{"a": {"x": 1}}]]])).is_err());
+        assert!(VariableType::try_from("Line")
+            .unwrap()
+            .resolve(&types)
+            .is_err());
+
+        assert!(table(&[("point", "{x: int}")]).is_err());
+        assert!(table(&[("String", "{x: int}")]).is_err());
+        assert!(table(&[("Point", "(int, int)")]).is_err());
+        assert!(table(&[("Point", "{x: Line}")]).is_err());
+        let error = table(&[("A", "{b: B[]}"), ("B", "{a: A?}")]).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Type `A` contains itself (A -> B -> A), which isn't supported"
+        );
+    }
+
+    #[test]
+    fn test_check_value() {
+        let check = |ty: &str, value: Value| {
+            VariableType::try_from(ty)
+                .unwrap()
+                .check_value(&value)
+                .is_ok()
+        };
+
+        assert!(check("int", json!(1)));
+        assert!(!check("int", json!(1.5)));
+        assert!(check("float", json!(1)));
+        assert!(check("char", json!("é")));
+        assert!(!check("char", json!("ab")));
+        assert!(check("int?[]", json!([1, null])));
+        assert!(!check("int[]", json!([1, null])));
+        assert!(check("(int, string)", json!([1, "a"])));
+        assert!(!check("(int, string)", json!([1])));
+        assert!(check("{a: int, b: bool}", json!({"a": 1, "b": true})));
+        assert!(!check("{a: int, b: bool}", json!({"a": 1, "c": true})));
+        assert!(check("map<string, int>", json!([["a", 1], ["b", 2]])));
+        assert!(!check("map<string, int>", json!([["a", 1], ["a", 2]])));
+        assert!(!check("map<string, int>", json!({"a": 1})));
+    }
 }
@@ -1,2 +1,2 @@
 function urldecode() { : "${*//+/ }"; echo -e "${_//%/\\x}"; }
-timeout --signal=KILL ${TIMEOUT} gcc -o main src/main.c && timeout --signal=KILL ${TIMEOUT} ./main $(urldecode "${ARGS}")
\ No newline at end of file
+timeout --verbose --signal=KILL ${TIMEOUT} gcc -o main src/main.c && timeout --verbose --signal=KILL ${TIMEOUT} ./main $(urldecode "${ARGS}")
\ No newline at end of file
@@ -1,3 +1,2 @@
 function urldecode() { : "${*//+/ }"; echo -e "${_//%/\\x}"; }
-timeout --signal=KILL ${TIMEOUT} javac Main.java
-timeout --signal=KILL ${TIMEOUT} java -cp . Main $(urldecode "${ARGS}")
\ No newline at end of file
+timeout --verbose --signal=KILL ${TIMEOUT} javac -encoding UTF-8 *.java && timeout --verbose --signal=KILL ${TIMEOUT} java -cp . Main $(urldecode "${ARGS}")
\ No newline at end of file
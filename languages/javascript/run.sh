@@ -1,2 +1,2 @@
 function urldecode() { : "${*//+/ }"; echo -e "${_//%/\\x}"; }
-timeout --signal=KILL ${TIMEOUT} node -e "$(cat main.js)" $(urldecode "${ARGS}")
\ No newline at end of file
+timeout --verbose --signal=KILL ${TIMEOUT} node -e "$(cat main.js)" $(urldecode "${ARGS}")
\ No newline at end of file
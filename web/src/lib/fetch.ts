@@ -49,23 +49,59 @@ export type TestResult =
     | {
           type: "correct";
           stdout: string;
+          /** Set by the exercise's checker, if it has one */
+          message?: string;
       }
     | {
           type: "incorrect";
           stdout: string;
           output: Object;
+          message?: string;
       }
     | {
           type: "error";
           stdout: string;
           traceback: string;
+      }
+    | {
+          type: "time_limit_exceeded";
+          runtime_ms: number;
+      }
+    | {
+          type: "memory_limit_exceeded";
+      }
+    | {
+          type: "output_limit_exceeded";
+      }
+    | {
+          type: "runtime_error";
+          exit_code: number;
+          stderr: string;
       };
 
+/** A short, human readable description of a failed verdict */
+export function verdictName(result: TestResult): string | undefined {
+    switch (result.type) {
+        case "time_limit_exceeded":
+            return "Time Limit Exceeded";
+        case "memory_limit_exceeded":
+            return "Memory Limit Exceeded";
+        case "output_limit_exceeded":
+            return "Output Limit Exceeded";
+        case "runtime_error":
+            return "Runtime Error";
+    }
+}
+
 export class TestResults {
     [key: string]: {
         results: TestResult[];
-        hidden: boolean;
+        /** How many hidden cases passed, without saying which */
+        hidden: { passed: number; total: number };
         passed: boolean;
+        /** The weighted fraction of cases that passed, from 0 to 1 */
+        score: number;
+        weight: number;
     };
 }
 
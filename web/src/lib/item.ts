@@ -105,6 +105,8 @@ export function itemID() {
 import Quiz from "$cmpt/article/Quiz.svelte";
 import Code from "$cmpt/article/Code.svelte";
 import Admonition from "$cmpt/article/Admonition.svelte";
+import Tabs from "$cmpt/article/Tabs.svelte";
+import Collapsible from "$cmpt/article/Collapsible.svelte";
 
 export function renderArticle(body: HTMLElement, data?: ArticleData) {
     renderComponent(
@@ -127,6 +129,9 @@ export function renderArticle(body: HTMLElement, data?: ArticleData) {
         }
     );
     renderComponent(body, "admonition", Admonition);
+    // after the components that can be inside of them
+    renderComponent(body, "tabs", Tabs);
+    renderComponent(body, "collapsible", Collapsible);
     renderComponent(body, "quiz", Quiz, (props, slots) => {
         if (!data) return props;
         return {
@@ -176,12 +181,33 @@ export class ExerciseData {
                 output: string;
                 hidden_cases: number;
                 visible_cases: number;
+                /** How much the function counts towards the score */
+                weight: number;
+                compare:
+                    | { type: "exact" | "unordered" | "set" }
+                    | { type: "case_insensitive" | "whitespace" }
+                    | {
+                          type: "float";
+                          abs_epsilon: number;
+                          rel_epsilon: number;
+                      };
                 tests: {
                     inputs: Object[];
                     output: Object;
                 }[];
             };
         };
+        /** The content of the visible fixtures by path, shown read-only */
+        fixtures: { [path: string]: string };
+        /** Set for exercises where the code reads stdin and prints to stdout */
+        io?: {
+            args: string;
+            compare: { type: "exact" | "lines" | "whitespace" };
+            tests: {
+                stdin: string;
+                stdout: string;
+            }[];
+        };
     };
     lang_info: {
         [key: string]: {